@@ -0,0 +1,70 @@
+use {
+    crate::score::ScoreEvent,
+    std::{fmt, fmt::Display, str::FromStr},
+};
+
+/// The order an arpeggio steps through its notes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ArpMode {
+    /// Lowest to highest.
+    Up,
+    /// Highest to lowest.
+    Down,
+    /// Up and back down, without repeating the top and bottom notes.
+    UpDown,
+}
+
+impl FromStr for ArpMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
+        match s {
+            "up" => Ok(ArpMode::Up),
+            "down" => Ok(ArpMode::Down),
+            "updown" => Ok(ArpMode::UpDown),
+            _ => Err(anyhow::anyhow!("Unknown arpeggio mode")),
+        }
+    }
+}
+
+impl Display for ArpMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match &self {
+            ArpMode::Up => "up",
+            ArpMode::Down => "down",
+            ArpMode::UpDown => "updown",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// Cycles through `frequencies` in pitch order, one note every `1 / rate`
+/// seconds, for `seconds` in total.
+pub fn arpeggio(frequencies: &[f32], mode: ArpMode, rate: f32, seconds: f32) -> Vec<ScoreEvent> {
+    let mut sorted = frequencies.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let cycle: Vec<f32> = match mode {
+        ArpMode::Up => sorted,
+        ArpMode::Down => sorted.into_iter().rev().collect(),
+        ArpMode::UpDown if sorted.len() > 2 => {
+            let descent: Vec<f32> = sorted[1..sorted.len() - 1].iter().rev().copied().collect();
+            sorted.into_iter().chain(descent).collect()
+        }
+        ArpMode::UpDown => sorted,
+    };
+
+    let step = 1f32 / rate.max(f32::MIN_POSITIVE);
+    let steps = (seconds / step).ceil() as usize;
+
+    cycle
+        .iter()
+        .cycle()
+        .take(steps)
+        .enumerate()
+        .map(|(index, &frequency)| ScoreEvent {
+            frequency: Some(frequency),
+            duration: step.min(seconds - index as f32 * step),
+        })
+        .collect()
+}
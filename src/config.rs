@@ -0,0 +1,62 @@
+use {
+    serde::Deserialize,
+    std::path::{Path, PathBuf},
+};
+
+/// Personal defaults read from a TOML file. Flags given on the command line
+/// take precedence over environment variables, which take precedence over
+/// the file, which takes precedence over the built-in defaults.
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    waveform: Option<String>,
+    frequency: Option<u32>,
+    volume: Option<f32>,
+    sample_rate: Option<u32>,
+    device: Option<String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/gen-waveform/config.toml`, falling back to
+    /// `~/.config/gen-waveform/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(base.join("gen-waveform").join("config.toml"))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("failed to read {}: {}", path.display(), err))?;
+
+        toml::from_str(&text)
+            .map_err(|err| anyhow::anyhow!("invalid config {}: {}", path.display(), err))
+    }
+
+    /// The configured values as (argument id, value) pairs, to install as the
+    /// defaults of the matching flags.
+    pub fn defaults(&self) -> Vec<(&'static str, String)> {
+        let mut defaults = Vec::new();
+
+        if let Some(waveform) = &self.waveform {
+            defaults.push(("waveform", waveform.clone()));
+        }
+        if let Some(frequency) = self.frequency {
+            defaults.push(("frequency", frequency.to_string()));
+        }
+        if let Some(volume) = self.volume {
+            defaults.push(("volume", volume.to_string()));
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            defaults.push(("sample-rate", sample_rate.to_string()));
+        }
+        if let Some(device) = &self.device {
+            defaults.push(("device", device.clone()));
+        }
+
+        defaults
+    }
+}
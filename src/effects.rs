@@ -0,0 +1,2 @@
+pub mod delay;
+pub mod reverb;
@@ -0,0 +1,39 @@
+/// Feedback delay line: each echo is fed back into the line at `feedback`
+/// times its level and blended with the dry signal by `mix`.
+pub struct Delay {
+    bypass: bool,
+    feedback: f32,
+    mix: f32,
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Delay {
+    /// `feedback` is kept below 1 so the echoes always die away. A delay of
+    /// less than one sample or a `mix` of 0 bypasses the line entirely.
+    pub fn new(delay_ms: f32, feedback: f32, mix: f32, sample_rate: f32) -> Self {
+        let length = (delay_ms.max(0f32) * sample_rate / 1000f32) as usize;
+        let mix = mix.clamp(0f32, 1f32);
+        let bypass = length == 0 || mix == 0f32;
+
+        Self {
+            bypass,
+            feedback: feedback.clamp(0f32, 0.99),
+            mix,
+            buffer: vec![0f32; if bypass { 0 } else { length }],
+            position: 0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        if self.bypass {
+            return sample;
+        }
+
+        let delayed = self.buffer[self.position];
+        self.buffer[self.position] = sample + self.feedback * delayed;
+        self.position = (self.position + 1) % self.buffer.len();
+
+        (1f32 - self.mix) * sample + self.mix * delayed
+    }
+}
@@ -0,0 +1,127 @@
+/// Comb delays in samples at 44.1 kHz, from Freeverb.
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+/// Allpass delays in samples at 44.1 kHz, from Freeverb.
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+/// Level into the comb bank, which otherwise sums far above full scale.
+const INPUT_GAIN: f32 = 0.015;
+const DAMPING: f32 = 0.2;
+
+/// Freeverb-style mono reverb: eight damped feedback combs in parallel
+/// followed by four allpasses in series. The delay lines hold about 12,600
+/// samples at 44.1 kHz (roughly 50 KB), growing in proportion to the rate.
+pub struct Reverb {
+    bypass: bool,
+    wet: f32,
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl Reverb {
+    /// `room_size` from 0 to 1 lengthens the tail and `wet` from 0 to 1 sets
+    /// the blend of reverb into the output. A `wet` of 0 bypasses the reverb
+    /// without allocating its delay lines.
+    pub fn new(room_size: f32, wet: f32, sample_rate: f32) -> Self {
+        let wet = wet.clamp(0f32, 1f32);
+        let bypass = wet == 0f32;
+        let scale = |tuning: usize| ((tuning as f32 * sample_rate / 44100f32) as usize).max(1);
+        let feedback = 0.7 + 0.28 * room_size.clamp(0f32, 1f32);
+        let (combs, allpasses) = if bypass {
+            (Vec::new(), Vec::new())
+        } else {
+            (
+                COMB_TUNINGS
+                    .iter()
+                    .map(|&tuning| Comb::new(scale(tuning), feedback))
+                    .collect(),
+                ALLPASS_TUNINGS
+                    .iter()
+                    .map(|&tuning| Allpass::new(scale(tuning)))
+                    .collect(),
+            )
+        };
+
+        Self {
+            bypass,
+            wet,
+            combs,
+            allpasses,
+        }
+    }
+
+    /// Silences the tail, so the next render starts from a dry state.
+    pub fn reset(&mut self) {
+        for comb in &mut self.combs {
+            comb.buffer.iter_mut().for_each(|sample| *sample = 0f32);
+            comb.filtered = 0f32;
+        }
+        for allpass in &mut self.allpasses {
+            allpass.buffer.iter_mut().for_each(|sample| *sample = 0f32);
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        if self.bypass {
+            return sample;
+        }
+
+        let input = INPUT_GAIN * sample;
+        let mut output: f32 = self.combs.iter_mut().map(|comb| comb.process(input)).sum();
+        for allpass in &mut self.allpasses {
+            output = allpass.process(output);
+        }
+
+        (1f32 - self.wet) * sample + self.wet * output
+    }
+}
+
+/// Feedback comb with a one-pole low-pass in the loop, so high frequencies
+/// die away sooner.
+struct Comb {
+    buffer: Vec<f32>,
+    position: usize,
+    feedback: f32,
+    filtered: f32,
+}
+
+impl Comb {
+    fn new(length: usize, feedback: f32) -> Self {
+        Self {
+            buffer: vec![0f32; length],
+            position: 0,
+            feedback,
+            filtered: 0f32,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.buffer[self.position];
+        self.filtered = output * (1f32 - DAMPING) + self.filtered * DAMPING;
+        self.buffer[self.position] = input + self.feedback * self.filtered;
+        self.position = (self.position + 1) % self.buffer.len();
+
+        output
+    }
+}
+
+/// Schroeder allpass with a fixed gain of 0.5, diffusing the comb echoes.
+struct Allpass {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Allpass {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0f32; length],
+            position: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.position];
+        self.buffer[self.position] = input + 0.5 * delayed;
+        self.position = (self.position + 1) % self.buffer.len();
+
+        delayed - input
+    }
+}
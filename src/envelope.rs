@@ -0,0 +1,148 @@
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// Linear attack/decay/sustain/release amplitude envelope, advanced one
+/// sample at a time. Times are in seconds and `sustain` is a level from 0 to 1.
+pub struct Envelope {
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    sample_rate: f32,
+    stage: Stage,
+    level: f32,
+    release_step: f32,
+}
+
+impl Envelope {
+    pub fn new(attack: f32, decay: f32, sustain: f32, release: f32, sample_rate: f32) -> Self {
+        Self {
+            attack: attack.max(0f32),
+            decay: decay.max(0f32),
+            sustain: sustain.clamp(0f32, 1f32),
+            release: release.max(0f32),
+            sample_rate,
+            stage: Stage::Idle,
+            level: 0f32,
+            release_step: 0f32,
+        }
+    }
+
+    /// Restarts the attack from the current level, so retriggering doesn't click.
+    pub fn note_on(&mut self) {
+        self.stage = Stage::Attack;
+    }
+
+    pub fn note_off(&mut self) {
+        if self.stage != Stage::Idle {
+            self.stage = Stage::Release;
+            self.release_step = self.step(self.level, self.release);
+        }
+    }
+
+    pub fn next_level(&mut self) -> f32 {
+        match self.stage {
+            Stage::Idle => {}
+            Stage::Attack => {
+                self.level += self.step(1f32, self.attack);
+                if self.level >= 1f32 {
+                    self.level = 1f32;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level -= self.step(1f32 - self.sustain, self.decay);
+                if self.level <= self.sustain {
+                    self.level = self.sustain;
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => self.level = self.sustain,
+            Stage::Release => {
+                self.level -= self.release_step;
+                if self.level <= 0f32 {
+                    self.level = 0f32;
+                    self.stage = Stage::Idle;
+                }
+            }
+        }
+
+        self.level
+    }
+
+    /// Per-sample change that covers `distance` in `seconds`; a zero-length
+    /// stage completes in a single sample.
+    fn step(&self, distance: f32, seconds: f32) -> f32 {
+        if seconds > 0f32 {
+            distance / (seconds * self.sample_rate)
+        } else {
+            f32::INFINITY
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One sample per millisecond, so stage times in seconds read as
+    /// thousandths of a sample count.
+    const SAMPLE_RATE: f32 = 1000f32;
+
+    fn levels(envelope: &mut Envelope, n: usize) -> Vec<f32> {
+        (0..n).map(|_| envelope.next_level()).collect()
+    }
+
+    #[test]
+    fn attack_ramps_up_to_full_level() {
+        let mut envelope = Envelope::new(0.01, 0f32, 1f32, 0f32, SAMPLE_RATE);
+        envelope.note_on();
+        let attack = levels(&mut envelope, 10);
+
+        for pair in attack.windows(2) {
+            assert!(pair[1] > pair[0], "{:?}", attack);
+        }
+        assert!((attack[0] - 0.1).abs() < 1e-6, "{:?}", attack);
+        assert!((attack[9] - 1f32).abs() < 1e-6, "{:?}", attack);
+    }
+
+    #[test]
+    fn decays_to_and_holds_the_sustain_level() {
+        let mut envelope = Envelope::new(0.01, 0.02, 0.5, 0f32, SAMPLE_RATE);
+        envelope.note_on();
+        levels(&mut envelope, 40);
+
+        assert!(levels(&mut envelope, 100).iter().all(|&level| level == 0.5));
+    }
+
+    #[test]
+    fn release_falls_to_zero() {
+        let mut envelope = Envelope::new(0f32, 0f32, 0.8, 0.01, SAMPLE_RATE);
+        envelope.note_on();
+        levels(&mut envelope, 10);
+        envelope.note_off();
+        let release = levels(&mut envelope, 20);
+
+        for pair in release[..10].windows(2) {
+            assert!(pair[1] < pair[0], "{:?}", release);
+        }
+        assert!(
+            release[10..].iter().all(|&level| level == 0f32),
+            "{:?}",
+            release
+        );
+    }
+
+    #[test]
+    fn stays_silent_until_note_on() {
+        let mut envelope = Envelope::new(0f32, 0f32, 1f32, 0f32, SAMPLE_RATE);
+
+        assert!(levels(&mut envelope, 10).iter().all(|&level| level == 0f32));
+    }
+}
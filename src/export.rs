@@ -0,0 +1,60 @@
+use {std::io, std::io::Write, std::path::Path, std::time::Duration};
+
+/// Renders `duration` worth of samples to a mono 16-bit PCM WAV file without
+/// touching an audio device.
+pub fn write_wav<P, F>(
+    path: P,
+    next_sample: &mut F,
+    sample_rate: u32,
+    duration: Duration,
+) -> Result<(), anyhow::Error>
+where
+    P: AsRef<Path>,
+    F: FnMut() -> f32 + Send,
+{
+    let path = path.as_ref();
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)
+        .map_err(|err| anyhow::anyhow!("failed to create {}: {}", path.display(), err))?;
+    let total = (duration.as_secs_f64() * sample_rate as f64) as u64;
+
+    for _ in 0..total {
+        let value = next_sample().clamp(-1f32, 1f32);
+        writer.write_sample((value * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+
+    Ok(())
+}
+
+/// Prints `duration` worth of samples to stdout as `index,time,amplitude`
+/// lines, as fast as they render. A closed pipe ends the output quietly.
+pub fn write_csv<F>(
+    next_sample: &mut F,
+    sample_rate: u32,
+    duration: Duration,
+) -> Result<(), anyhow::Error>
+where
+    F: FnMut() -> f32 + Send,
+{
+    let total = (duration.as_secs_f64() * sample_rate as f64) as u64;
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+
+    let result = writeln!(stdout, "index,time,amplitude").and_then(|_| {
+        for index in 0..total {
+            let time = index as f64 / sample_rate as f64;
+            writeln!(stdout, "{},{},{}", index, time, next_sample())?;
+        }
+        stdout.flush()
+    });
+
+    match result {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
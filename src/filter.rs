@@ -0,0 +1,48 @@
+use std::f32::consts::PI;
+
+/// Resonant two-pole low-pass filter, a trapezoidal-integrated state
+/// variable filter that stays stable up to Nyquist.
+pub struct LowPass {
+    bypass: bool,
+    a1: f32,
+    a2: f32,
+    a3: f32,
+    ic1eq: f32,
+    ic2eq: f32,
+}
+
+impl LowPass {
+    /// `resonance` runs from 0 (Q of 0.5, no peak) towards 1 (a sharp peak
+    /// just short of self-oscillation). A cutoff at or above Nyquist bypasses
+    /// the filter entirely.
+    pub fn new(cutoff: f32, resonance: f32, sample_rate: f32) -> Self {
+        let bypass = cutoff >= sample_rate / 2f32;
+        let g = (PI * cutoff.max(1f32) / sample_rate).tan();
+        let k = 2f32 - 1.95 * resonance.clamp(0f32, 1f32);
+        let a1 = 1f32 / (1f32 + g * (g + k));
+        let a2 = g * a1;
+
+        Self {
+            bypass,
+            a1,
+            a2,
+            a3: g * a2,
+            ic1eq: 0f32,
+            ic2eq: 0f32,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        if self.bypass {
+            return sample;
+        }
+
+        let v3 = sample - self.ic2eq;
+        let v1 = self.a1 * self.ic1eq + self.a2 * v3;
+        let v2 = self.ic2eq + self.a2 * self.ic1eq + self.a3 * v3;
+        self.ic1eq = 2f32 * v1 - self.ic1eq;
+        self.ic2eq = 2f32 * v2 - self.ic2eq;
+
+        v2
+    }
+}
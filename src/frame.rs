@@ -0,0 +1,28 @@
+use {crate::tcp::SampleStream, std::f32::consts::PI};
+
+/// Applies equal-power panning, so the summed power stays constant as the
+/// signal moves between the left and right channels.
+pub fn panned(
+    mut next_frame: Box<dyn FnMut() -> (f32, f32) + Send>,
+    pan: f32,
+) -> Box<dyn FnMut() -> (f32, f32) + Send> {
+    let theta = (pan.clamp(-1f32, 1f32) + 1f32) * PI / 4f32;
+    let (left_gain, right_gain) = (theta.cos(), theta.sin());
+
+    Box::new(move || {
+        let (left, right) = next_frame();
+        (left_gain * left, right_gain * right)
+    })
+}
+
+/// Sends the mono sum of each frame to the clients of `stream`.
+pub fn streamed(
+    mut next_frame: Box<dyn FnMut() -> (f32, f32) + Send>,
+    mut stream: SampleStream,
+) -> Box<dyn FnMut() -> (f32, f32) + Send> {
+    Box::new(move || {
+        let (left, right) = next_frame();
+        stream.push(0.5 * (left + right));
+        (left, right)
+    })
+}
@@ -0,0 +1,107 @@
+use {
+    crate::Waveform,
+    rand::{rngs::StdRng, Rng},
+    std::f32::consts::PI,
+    std::fmt,
+    std::fmt::Display,
+    std::str::FromStr,
+};
+
+/// What the LFO modulates.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ModTarget {
+    /// Vibrato: the frequency is scaled by up to `depth` octaves either way.
+    Frequency,
+    /// Tremolo: the gain dips by up to `depth` below full level.
+    Volume,
+    None,
+}
+
+impl FromStr for ModTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
+        match s {
+            "freq" => Ok(ModTarget::Frequency),
+            "volume" => Ok(ModTarget::Volume),
+            "none" => Ok(ModTarget::None),
+            _ => Err(anyhow::anyhow!("Unknown LFO target")),
+        }
+    }
+}
+
+impl Display for ModTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match &self {
+            ModTarget::Frequency => "freq",
+            ModTarget::Volume => "volume",
+            ModTarget::None => "none",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// Low-frequency oscillator with its own phase accumulator. Shapes are
+/// naive since aliasing is irrelevant at sub-audio rates; noise holds a
+/// random value for each cycle.
+pub struct Lfo {
+    waveform: Waveform,
+    rate: f32,
+    depth: f32,
+    sample_rate: f32,
+    phase: f32,
+    held: f32,
+    rng: StdRng,
+}
+
+impl Lfo {
+    pub fn new(waveform: Waveform, rate: f32, depth: f32, sample_rate: f32, rng: StdRng) -> Self {
+        Self {
+            waveform,
+            rate: rate.max(0f32),
+            depth: depth.clamp(0f32, 1f32),
+            sample_rate,
+            phase: 0f32,
+            held: 0f32,
+            rng,
+        }
+    }
+
+    /// Next LFO value, from -1 to 1.
+    pub fn next_value(&mut self) -> f32 {
+        let phase = self.phase;
+        let value = match self.waveform {
+            // The LFO has no table of its own, so a wavetable LFO is a sine.
+            Waveform::SINE | Waveform::WAVETABLE => (2f32 * PI * phase).sin(),
+            Waveform::SAWTOOTH => 1f32 - 2f32 * phase,
+            Waveform::TRIANGLE => 1f32 - 4f32 * (phase - 0.5).abs(),
+            Waveform::SQUARE | Waveform::PULSE => {
+                if phase < 0.5 {
+                    1f32
+                } else {
+                    -1f32
+                }
+            }
+            Waveform::NOISE => self.held,
+        };
+
+        self.phase += self.rate / self.sample_rate;
+        if self.phase >= 1f32 {
+            self.phase -= self.phase.floor();
+            self.held = self.rng.gen::<f32>() * 2f32 - 1f32;
+        }
+
+        value
+    }
+
+    /// Frequency multiplier for vibrato.
+    pub fn frequency_ratio(&mut self) -> f32 {
+        2f32.powf(self.depth * self.next_value())
+    }
+
+    /// Gain multiplier for tremolo, between `1 - depth` and 1.
+    pub fn gain(&mut self) -> f32 {
+        1f32 - self.depth * (1f32 - self.next_value()) / 2f32
+    }
+}
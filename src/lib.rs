@@ -0,0 +1,1001 @@
+pub mod arp;
+pub mod effects;
+pub mod envelope;
+pub mod export;
+pub mod filter;
+pub mod frame;
+pub mod lfo;
+pub mod noise;
+pub mod notes;
+pub mod oscillator;
+pub mod raw;
+pub mod score;
+pub mod source_file;
+pub mod sweep;
+pub mod tcp;
+pub mod voice_bank;
+pub mod wavetable;
+
+pub use oscillator::Oscillator;
+
+use {
+    rand::{rngs::StdRng, Rng},
+    std::f32::consts::{FRAC_1_SQRT_2, PI},
+    std::fmt,
+    std::fmt::Display,
+    std::str::FromStr,
+    std::time::Duration,
+    wavetable::Wavetable,
+};
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Copy, Clone)]
+pub enum Waveform {
+    SINE,
+    SAWTOOTH,
+    TRIANGLE,
+    SQUARE,
+    PULSE,
+    NOISE,
+    /// A single cycle loaded from a file with --wavetable.
+    WAVETABLE,
+}
+
+impl FromStr for Waveform {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
+        match s {
+            "sine" => Ok(Waveform::SINE),
+            "sin" => Ok(Waveform::SINE),
+            "sawtooth" => Ok(Waveform::SAWTOOTH),
+            "saw" => Ok(Waveform::SAWTOOTH),
+            "triangle" => Ok(Waveform::TRIANGLE),
+            "tri" => Ok(Waveform::TRIANGLE),
+            "square" => Ok(Waveform::SQUARE),
+            "squ" => Ok(Waveform::SQUARE),
+            "pulse" => Ok(Waveform::PULSE),
+            "pul" => Ok(Waveform::PULSE),
+            "noise" => Ok(Waveform::NOISE),
+            "noi" => Ok(Waveform::NOISE),
+            "wavetable" => Ok(Waveform::WAVETABLE),
+            "wtb" => Ok(Waveform::WAVETABLE),
+            _ => Err(anyhow::anyhow!("Unknown waveform")),
+        }
+    }
+}
+
+impl Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match &self {
+            Waveform::SINE => "sine",
+            Waveform::SAWTOOTH => "sawtooth",
+            Waveform::TRIANGLE => "triangle",
+            Waveform::SQUARE => "square",
+            Waveform::PULSE => "pulse",
+            Waveform::NOISE => "noise",
+            Waveform::WAVETABLE => "wavetable",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+impl Waveform {
+    /// RMS of a full-scale sine.
+    const SINE_RMS: f32 = FRAC_1_SQRT_2;
+    /// Additive sawtooth: partials of amplitude 1/n, so sqrt(sum(1/n^2) / 2)
+    /// = sqrt(pi^2 / 12) as the partial count grows.
+    #[cfg(feature = "band-limited")]
+    const SAWTOOTH_ADDITIVE_RMS: f32 = 0.9069;
+    /// Additive square: odd partials of amplitude 1/n, so sqrt(pi^2 / 16).
+    #[cfg(feature = "band-limited")]
+    const SQUARE_ADDITIVE_RMS: f32 = std::f32::consts::FRAC_PI_4;
+    /// Additive triangle: odd partials of amplitude 1/n^2, so
+    /// sqrt(pi^4 / 96 / 2).
+    #[cfg(feature = "band-limited")]
+    const TRIANGLE_ADDITIVE_RMS: f32 = 0.7123;
+    /// Naive sawtooth and triangle: linear ramps across -1 to 1, so 1/sqrt(3).
+    const RAMP_RMS: f32 = 0.5774;
+    /// The level `normalization` brings every waveform to: that of the naive
+    /// ramps, the quietest of the shapes, so that it only ever attenuates.
+    const REFERENCE_RMS: f32 = Waveform::RAMP_RMS;
+
+    /// Gain that brings the waveform's RMS down to that of the quietest
+    /// shape, so switching waveforms at the same volume keeps roughly the
+    /// same loudness without boosting any of them. Noise of every color is as
+    /// loud as a sine (see `noise::NoiseColor`), and so is an FM voice; wavetables
+    /// are treated as loud as a sine. Additive pulses with a duty cycle below
+    /// about 0.16 or above 0.84 are quieter still and are left unchanged.
+    pub fn normalization(self, anti_aliasing: AntiAliasing, duty_cycle: f32) -> f32 {
+        (Waveform::REFERENCE_RMS / self.rms(anti_aliasing, duty_cycle)).min(1f32)
+    }
+
+    /// `normalization` for an FM voice of `ratio` and `index`. Its carrier
+    /// only moves in phase, so it is as loud as a sine unless k = 2 / ratio is
+    /// a whole number: then the mean of its square moves by
+    /// -(-1)^k J_k(2 index) / 2, e.g. to 0.36 from 0.5 at a ratio of 2 and an
+    /// index of 3. Like the shapes, a voice quieter than the reference is left
+    /// unchanged.
+    pub fn fm_normalization(ratio: f32, index: f32) -> f32 {
+        let k = 2f32 / ratio;
+        let mean_square = if ratio > 0f32 && (k - k.round()).abs() < 1e-4 {
+            let k = k.round() as u32;
+            0.5 * (1f32 - (-1f32).powi(k as i32) * bessel_j(k, 2f32 * index))
+        } else {
+            0.5
+        };
+
+        (Waveform::REFERENCE_RMS / mean_square.sqrt()).min(1f32)
+    }
+
+    /// The level in dBFS that `normalization` plays every waveform at, where
+    /// 0 dBFS is a sine peaking at full scale: 20 log10(1/sqrt(3) / (1/sqrt(2)))
+    /// or about -1.76 dB.
+    pub fn normalized_level_dbfs() -> f32 {
+        20f32 * (Waveform::REFERENCE_RMS / Waveform::SINE_RMS).log10()
+    }
+
+    #[cfg_attr(not(feature = "band-limited"), allow(unused_variables))]
+    fn rms(self, anti_aliasing: AntiAliasing, duty_cycle: f32) -> f32 {
+        match (self, anti_aliasing) {
+            (Waveform::SINE, _) | (Waveform::NOISE, _) | (Waveform::WAVETABLE, _) => {
+                Waveform::SINE_RMS
+            }
+            #[cfg(feature = "band-limited")]
+            (Waveform::SAWTOOTH, AntiAliasing::Additive) => Waveform::SAWTOOTH_ADDITIVE_RMS,
+            #[cfg(feature = "band-limited")]
+            (Waveform::SQUARE, AntiAliasing::Additive) => Waveform::SQUARE_ADDITIVE_RMS,
+            #[cfg(feature = "band-limited")]
+            (Waveform::TRIANGLE, AntiAliasing::Additive) => Waveform::TRIANGLE_ADDITIVE_RMS,
+            // Two levels, pi(1 - d)/2 and -pi d/2, held for d and 1 - d of
+            // each cycle.
+            #[cfg(feature = "band-limited")]
+            (Waveform::PULSE, AntiAliasing::Additive) => {
+                let duty_cycle = duty_cycle.clamp(0.01, 0.99);
+                PI / 2f32 * (duty_cycle * (1f32 - duty_cycle)).sqrt()
+            }
+            (Waveform::SAWTOOTH, _) | (Waveform::TRIANGLE, _) => Waveform::RAMP_RMS,
+            (Waveform::SQUARE, _) | (Waveform::PULSE, _) => 1f32,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Dtmf {
+    pub low: u32,
+    pub high: u32,
+}
+
+impl Dtmf {
+    const ROWS: [u32; 4] = [697, 770, 852, 941];
+    const COLUMNS: [u32; 4] = [1209, 1336, 1477, 1633];
+    const KEYPAD: [[char; 4]; 4] = [
+        ['1', '2', '3', 'A'],
+        ['4', '5', '6', 'B'],
+        ['7', '8', '9', 'C'],
+        ['*', '0', '#', 'D'],
+    ];
+
+    pub fn tone(self, sample_rate: f32) -> Box<dyn FnMut() -> f32 + Send> {
+        let mut low = WaveformRequest::new(self.low as f32, 0f32, sample_rate).sine();
+        let mut high = WaveformRequest::new(self.high as f32, 0f32, sample_rate).sine();
+
+        Box::new(move || 0.5 * (low() + high()))
+    }
+}
+
+impl FromStr for Dtmf {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
+        let mut chars = s.chars();
+        let digit = match (chars.next(), chars.next()) {
+            (Some(c), None) => c.to_ascii_uppercase(),
+            _ => return Err(anyhow::anyhow!("DTMF digit must be a single character")),
+        };
+
+        for (row, keys) in Dtmf::KEYPAD.iter().enumerate() {
+            if let Some(column) = keys.iter().position(|&key| key == digit) {
+                return Ok(Dtmf {
+                    low: Dtmf::ROWS[row],
+                    high: Dtmf::COLUMNS[column],
+                });
+            }
+        }
+
+        Err(anyhow::anyhow!("Unknown DTMF digit"))
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Copy, Clone)]
+pub enum Alignment {
+    EBU,
+    SMPTE,
+}
+
+impl Alignment {
+    pub const FREQUENCY: f32 = 1000f32;
+
+    /// Level of the tone in dBFS, where 0 dBFS is a sine peaking at full scale.
+    fn level_dbfs(self) -> f32 {
+        match self {
+            Alignment::EBU => -18f32,
+            Alignment::SMPTE => -20f32,
+        }
+    }
+
+    pub fn tone(self, sample_rate: f32) -> Box<dyn FnMut() -> f32 + Send> {
+        let gain = 10f32.powf(self.level_dbfs() / 20f32);
+        let mut sine = WaveformRequest::new(Alignment::FREQUENCY, 0f32, sample_rate).sine();
+
+        Box::new(move || gain * sine())
+    }
+}
+
+impl FromStr for Alignment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
+        match s {
+            "ebu" => Ok(Alignment::EBU),
+            "smpte" => Ok(Alignment::SMPTE),
+            _ => Err(anyhow::anyhow!("Unknown alignment standard")),
+        }
+    }
+}
+
+/// How the discontinuous waveforms (sawtooth, square, pulse) avoid aliasing.
+#[derive(Debug, Copy, Clone)]
+pub enum AntiAliasing {
+    /// Sum of sine harmonics, up to --max-harmonics.
+    #[cfg(feature = "band-limited")]
+    Additive,
+    /// Naive shape with a polynomial correction at each discontinuity.
+    PolyBlep,
+    /// Naive shape, aliasing freely.
+    None,
+}
+
+/// PolyBLEP in band-limited builds: it costs a few operations per sample
+/// where the additive sum costs a `sin()` per partial.
+impl Default for AntiAliasing {
+    #[cfg(feature = "band-limited")]
+    fn default() -> Self {
+        AntiAliasing::PolyBlep
+    }
+
+    #[cfg(not(feature = "band-limited"))]
+    fn default() -> Self {
+        AntiAliasing::None
+    }
+}
+
+impl FromStr for AntiAliasing {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
+        match s {
+            #[cfg(feature = "band-limited")]
+            "additive" => Ok(AntiAliasing::Additive),
+            "polyblep" => Ok(AntiAliasing::PolyBlep),
+            "none" => Ok(AntiAliasing::None),
+            _ => Err(anyhow::anyhow!("Unknown anti-aliasing mode")),
+        }
+    }
+}
+
+impl Display for AntiAliasing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match &self {
+            #[cfg(feature = "band-limited")]
+            AntiAliasing::Additive => "additive",
+            AntiAliasing::PolyBlep => "polyblep",
+            AntiAliasing::None => "none",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+pub struct WaveformRequest {
+    frequency: f32,
+    sample_clock: f32,
+    sample_rate: f32,
+    clock_step: f32,
+    cycle_offset: f32,
+    frequency_modulation: Option<Box<dyn FnMut() -> f32 + Send>>,
+    anti_aliasing: AntiAliasing,
+    #[cfg(feature = "band-limited")]
+    max_harmonics: u32,
+    #[cfg(feature = "band-limited")]
+    adaptive_harmonics: bool,
+}
+
+impl WaveformRequest {
+    pub fn new(frequency: f32, sample_clock: f32, sample_rate: f32) -> Self {
+        Self {
+            frequency,
+            sample_clock,
+            sample_rate,
+            clock_step: 1f32,
+            cycle_offset: 0f32,
+            frequency_modulation: None,
+            anti_aliasing: AntiAliasing::default(),
+            #[cfg(feature = "band-limited")]
+            max_harmonics: 49,
+            #[cfg(feature = "band-limited")]
+            adaptive_harmonics: false,
+        }
+    }
+
+    /// Scales the frequency every sample by the ratio `modulation` returns.
+    pub fn with_frequency_modulation(mut self, modulation: Box<dyn FnMut() -> f32 + Send>) -> Self {
+        self.frequency_modulation = Some(modulation);
+        self
+    }
+
+    pub fn with_anti_aliasing(mut self, anti_aliasing: AntiAliasing) -> Self {
+        self.anti_aliasing = anti_aliasing;
+        self
+    }
+
+    /// PolyBLEP when `band_limited`, otherwise the naive shapes.
+    pub fn with_band_limited(self, band_limited: bool) -> Self {
+        self.with_anti_aliasing(if band_limited {
+            AntiAliasing::PolyBlep
+        } else {
+            AntiAliasing::None
+        })
+    }
+
+    #[cfg(feature = "band-limited")]
+    pub fn with_max_harmonics(mut self, max_harmonics: u32) -> Self {
+        self.max_harmonics = max_harmonics;
+        self
+    }
+
+    #[cfg(feature = "band-limited")]
+    pub fn with_adaptive_harmonics(mut self, adaptive_harmonics: bool) -> Self {
+        self.adaptive_harmonics = adaptive_harmonics;
+        self
+    }
+
+    /// Highest partial the additive waveforms may sum at the instantaneous
+    /// frequency, which vibrato and sweeps change every sample.
+    #[cfg(feature = "band-limited")]
+    fn partial_limit(&self) -> u32 {
+        if self.adaptive_harmonics {
+            (self.sample_rate / 2f32 / (self.frequency * self.clock_step)) as u32
+        } else {
+            u32::MAX
+        }
+    }
+
+    fn base_waveform(&mut self, value: f32, frequency: f32, sample_rate: f32) -> f32 {
+        (2f32 * PI * frequency * self.sample_clock * value / self.sample_rate
+            + 2f32 * PI * value * self.cycle_offset
+            + sample_rate)
+            .sin()
+    }
+
+    /// Advances the clock by one sample, or by the modulation ratio so that a
+    /// changing frequency keeps the phase continuous.
+    fn tick(&mut self) {
+        if let Some(modulation) = &mut self.frequency_modulation {
+            self.clock_step = modulation();
+        }
+
+        let clock = self.sample_clock + self.clock_step;
+        if clock >= self.sample_rate {
+            // Wrapping the clock drops `frequency` cycles of phase; carry the
+            // fractional part so non-integer frequencies stay continuous.
+            self.cycle_offset = (self.cycle_offset + self.frequency).fract();
+        }
+        self.sample_clock = clock % self.sample_rate;
+    }
+
+    fn phase(&self) -> f32 {
+        (self.frequency * self.sample_clock / self.sample_rate + self.cycle_offset).fract()
+    }
+
+    fn phase_increment(&self) -> f32 {
+        self.frequency * self.clock_step / self.sample_rate
+    }
+
+    pub fn sine(mut self) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+            self.base_waveform(1f32, self.frequency, 0f32)
+        })
+    }
+
+    pub fn sawtooth(self) -> Box<dyn FnMut() -> f32 + Send> {
+        match self.anti_aliasing {
+            #[cfg(feature = "band-limited")]
+            AntiAliasing::Additive => self.sawtooth_additive(),
+            AntiAliasing::PolyBlep => self.sawtooth_polyblep(),
+            AntiAliasing::None => self.sawtooth_naive(),
+        }
+    }
+
+    pub fn square(self) -> Box<dyn FnMut() -> f32 + Send> {
+        match self.anti_aliasing {
+            #[cfg(feature = "band-limited")]
+            AntiAliasing::Additive => self.square_additive(),
+            AntiAliasing::PolyBlep => self.pulse_polyblep(0.5),
+            AntiAliasing::None => self.pulse_naive(0.5),
+        }
+    }
+
+    /// The triangle has no discontinuity to correct, so PolyBLEP plays it naive.
+    pub fn triangle(self) -> Box<dyn FnMut() -> f32 + Send> {
+        match self.anti_aliasing {
+            #[cfg(feature = "band-limited")]
+            AntiAliasing::Additive => self.triangle_additive(),
+            AntiAliasing::PolyBlep | AntiAliasing::None => self.triangle_naive(),
+        }
+    }
+
+    pub fn pulse(self, duty_cycle: f32) -> Box<dyn FnMut() -> f32 + Send> {
+        let duty_cycle = duty_cycle.clamp(0.01, 0.99);
+
+        match self.anti_aliasing {
+            #[cfg(feature = "band-limited")]
+            AntiAliasing::Additive => self.pulse_additive(duty_cycle),
+            AntiAliasing::PolyBlep => self.pulse_polyblep(duty_cycle),
+            AntiAliasing::None => self.pulse_naive(duty_cycle),
+        }
+    }
+
+    #[cfg(feature = "band-limited")]
+    fn sawtooth_additive(mut self) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+            let harmonics = self.max_harmonics.min(self.partial_limit());
+            let mut result = 0f32;
+
+            for n in 1..=harmonics {
+                result += 1f32 / n as f32 * self.base_waveform(n as f32, self.frequency, 0f32);
+            }
+
+            result
+        })
+    }
+
+    fn sawtooth_polyblep(mut self) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+            let phase = self.phase();
+
+            1f32 - 2f32 * phase + poly_blep(phase, self.phase_increment())
+        })
+    }
+
+    fn sawtooth_naive(mut self) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+            1f32 - 2f32 * self.phase()
+        })
+    }
+
+    #[cfg(feature = "band-limited")]
+    fn square_additive(mut self) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+            let harmonics = self.max_harmonics.min(self.partial_limit());
+            let mut result = 0f32;
+
+            for n in (1..=harmonics).step_by(2) {
+                result += 1f32 / n as f32 * self.base_waveform(n as f32, self.frequency, 0f32);
+            }
+
+            result
+        })
+    }
+
+    /// Sums the odd terms n up to `max_harmonics`, each at n^2 times the
+    /// frequency. That passes Nyquist within a few terms, so whether or not
+    /// harmonics are adaptive the sum stops before it.
+    #[cfg(feature = "band-limited")]
+    fn triangle_additive(mut self) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+            let nyquist = self.sample_rate / 2f32 / (self.frequency * self.clock_step);
+            let terms = self.max_harmonics.min(nyquist.sqrt() as u32);
+            let mut result = 0f32;
+
+            for n in (1..=terms).step_by(2) {
+                let p = (n as f32).powi(2);
+                result += 1f32 / p * self.base_waveform(p, self.frequency, 0f32);
+            }
+
+            result
+        })
+    }
+
+    fn triangle_naive(mut self) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+            let phase = self.phase();
+
+            if phase < 0.25 {
+                4f32 * phase
+            } else if phase < 0.75 {
+                2f32 - 4f32 * phase
+            } else {
+                4f32 * phase - 4f32
+            }
+        })
+    }
+
+    /// At a duty cycle of 0.5 this matches `square_additive`.
+    #[cfg(feature = "band-limited")]
+    fn pulse_additive(mut self, duty_cycle: f32) -> Box<dyn FnMut() -> f32 + Send> {
+        let shift = 2f32 * PI * duty_cycle;
+
+        Box::new(move || {
+            self.tick();
+            let harmonics = self.max_harmonics.min(self.partial_limit());
+            let mut result = 0f32;
+
+            // Difference of two band-limited sawtooths offset by the duty cycle.
+            for n in 1..=harmonics {
+                let n = n as f32;
+                let saw = self.base_waveform(n, self.frequency, 0f32);
+                let shifted = self.base_waveform(n, self.frequency, -n * shift);
+                result += 0.5 / n * (saw - shifted);
+            }
+
+            result
+        })
+    }
+
+    fn pulse_polyblep(mut self, duty_cycle: f32) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+            let phase = self.phase();
+            let increment = self.phase_increment();
+            let naive = if phase < duty_cycle { 1f32 } else { -1f32 };
+
+            naive + poly_blep(phase, increment)
+                - poly_blep((phase + 1f32 - duty_cycle).fract(), increment)
+        })
+    }
+
+    fn pulse_naive(mut self, duty_cycle: f32) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+
+            if self.phase() < duty_cycle {
+                1f32
+            } else {
+                -1f32
+            }
+        })
+    }
+
+    /// Reads `table` at the oscillator's phase, so it plays one cycle of the
+    /// table per period of `frequency`.
+    pub fn wavetable(mut self, table: Wavetable) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+            table.at(self.phase())
+        })
+    }
+
+    /// With an index of 0 this is exactly `sine`.
+    pub fn fm(mut self, ratio: f32, index: f32) -> Box<dyn FnMut() -> f32 + Send> {
+        let mut modulator_phase = 0f32;
+
+        Box::new(move || {
+            self.tick();
+            modulator_phase = (modulator_phase + ratio * self.phase_increment()).fract();
+            let modulator = index * (2f32 * PI * modulator_phase).sin();
+
+            self.base_waveform(1f32, self.frequency, modulator)
+        })
+    }
+
+    pub fn white_noise(mut self, mut rng: StdRng) -> Box<dyn FnMut() -> f32 + Send> {
+        Box::new(move || {
+            self.tick();
+            let seed = rng.gen::<u32>();
+            let theta = seed as f32 / u32::MAX as f32 * 2f32 * PI;
+
+            self.base_waveform(1f32, theta, theta)
+        })
+    }
+}
+
+/// Correction that rounds off a jump from -1 to 1 at phase 0 over one sample
+/// on either side; add it to a naive waveform at each rising edge.
+fn poly_blep(phase: f32, increment: f32) -> f32 {
+    if phase < increment {
+        let t = phase / increment;
+        2f32 * t - t * t - 1f32
+    } else if phase > 1f32 - increment {
+        let t = (phase - 1f32) / increment;
+        t * t + 2f32 * t + 1f32
+    } else {
+        0f32
+    }
+}
+
+/// Bessel function of the first kind, J_n(x) = 1/(2 pi) times the integral of
+/// cos(n t - x sin(t)) over a period, which sampling evenly converges on once
+/// the samples outnumber n + |x|.
+fn bessel_j(n: u32, x: f32) -> f32 {
+    let samples = 64 + 2 * (n + x.abs() as u32);
+    let sum: f32 = (0..samples)
+        .map(|sample| {
+            let t = 2f32 * PI * sample as f32 / samples as f32;
+            (n as f32 * t - x * t.sin()).cos()
+        })
+        .sum();
+
+    sum / samples as f32
+}
+
+/// Renders `duration` worth of samples into memory without an audio device,
+/// e.g. from a `WaveformRequest` waveform.
+pub fn render<F>(next_sample: &mut F, sample_rate: u32, duration: Duration) -> Vec<f32>
+where
+    F: FnMut() -> f32,
+{
+    let total = (duration.as_secs_f64() * sample_rate as f64) as usize;
+
+    render_samples(next_sample, total)
+}
+
+/// Renders exactly `n` samples into memory, e.g. for analysis or tests.
+pub fn render_samples<F>(next_sample: &mut F, n: usize) -> Vec<f32>
+where
+    F: FnMut() -> f32,
+{
+    (0..n).map(|_| next_sample()).collect()
+}
+
+/// Holds each of `steps` evenly spaced DC levels from -1 to 1 for
+/// `step_duration` seconds, then starts over from -1.
+pub fn staircase(
+    steps: u32,
+    step_duration: f32,
+    sample_rate: f32,
+) -> Box<dyn FnMut() -> f32 + Send> {
+    let hold = ((step_duration * sample_rate) as u64).max(1);
+    let mut step = 0u32;
+    let mut held = 0u64;
+
+    Box::new(move || {
+        let level = if steps > 1 {
+            -1f32 + 2f32 * step as f32 / (steps - 1) as f32
+        } else {
+            0f32
+        };
+
+        held += 1;
+        if held == hold {
+            held = 0;
+            step = (step + 1) % steps.max(1);
+        }
+
+        level
+    })
+}
+
+/// Saturates with tanh so the output never leaves ±1.0. Quiet signals pass
+/// almost untouched, but the curve bends well before full scale: a sine
+/// peaking at 1.0 comes out peaking at about 0.76.
+pub fn soft_clip(sample: f32) -> f32 {
+    sample.tanh()
+}
+
+/// Wraps a generator to report sample-to-sample jumps above `threshold`, which
+/// are usually heard as clicks.
+pub fn detect_clicks(
+    mut next_sample: Box<dyn FnMut() -> f32 + Send>,
+    threshold: f32,
+    sample_rate: f32,
+    context: String,
+) -> Box<dyn FnMut() -> f32 + Send> {
+    let mut clicks = Clicks::new(threshold, sample_rate, context);
+
+    Box::new(move || {
+        let value = next_sample();
+        clicks.check(value);
+        value
+    })
+}
+
+/// `detect_clicks` for a generator of (left, right) frames, checking each
+/// channel on its own.
+pub fn detect_frame_clicks(
+    mut next_frame: Box<dyn FnMut() -> (f32, f32) + Send>,
+    threshold: f32,
+    sample_rate: f32,
+    context: String,
+) -> Box<dyn FnMut() -> (f32, f32) + Send> {
+    let mut left = Clicks::new(threshold, sample_rate, format!("{}, left", context));
+    let mut right = Clicks::new(threshold, sample_rate, format!("{}, right", context));
+
+    Box::new(move || {
+        let (left_value, right_value) = next_frame();
+        left.check(left_value);
+        right.check(right_value);
+        (left_value, right_value)
+    })
+}
+
+/// The state of one channel watched by `detect_clicks`.
+struct Clicks {
+    threshold: f32,
+    sample_rate: f32,
+    context: String,
+    previous: Option<f32>,
+    index: u64,
+    count: u64,
+}
+
+impl Clicks {
+    fn new(threshold: f32, sample_rate: f32, context: String) -> Self {
+        Self {
+            threshold,
+            sample_rate,
+            context,
+            previous: None,
+            index: 0,
+            count: 0,
+        }
+    }
+
+    /// Reports the jump from the previous sample to `value` if it is above
+    /// the threshold.
+    fn check(&mut self, value: f32) {
+        if let Some(previous) = self.previous {
+            let jump = (value - previous).abs();
+            if jump > self.threshold {
+                self.count += 1;
+                eprintln!(
+                    "discontinuity #{} at {:.4}s (sample {}): jump of {:.4} ({})",
+                    self.count,
+                    self.index as f32 / self.sample_rate,
+                    self.index,
+                    jump,
+                    self.context
+                );
+            }
+        }
+        self.previous = Some(value);
+        self.index += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, rand::SeedableRng};
+
+    const SAMPLE_RATE: f32 = 48000f32;
+    const SEED: u64 = 42;
+
+    /// `n` samples of `waveform` at `frequency`, starting from phase zero and
+    /// with noise seeded so that every run renders the same signal.
+    fn render_waveform(
+        waveform: Waveform,
+        anti_aliasing: AntiAliasing,
+        frequency: f32,
+        n: usize,
+    ) -> Vec<f32> {
+        let request =
+            WaveformRequest::new(frequency, 0f32, SAMPLE_RATE).with_anti_aliasing(anti_aliasing);
+        let mut oscillator = match waveform {
+            Waveform::SINE => request.sine(),
+            Waveform::SAWTOOTH => request.sawtooth(),
+            Waveform::TRIANGLE => request.triangle(),
+            Waveform::SQUARE => request.square(),
+            Waveform::PULSE => request.pulse(0.5),
+            Waveform::NOISE => request.white_noise(StdRng::seed_from_u64(SEED)),
+            Waveform::WAVETABLE => unreachable!("wavetables are loaded from a file"),
+        };
+
+        render_samples(&mut oscillator, n)
+    }
+
+    fn anti_aliasing_modes() -> Vec<AntiAliasing> {
+        vec![
+            #[cfg(feature = "band-limited")]
+            AntiAliasing::Additive,
+            AntiAliasing::PolyBlep,
+            AntiAliasing::None,
+        ]
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples
+            .iter()
+            .fold(0f32, |peak, sample| peak.max(sample.abs()))
+    }
+
+    #[test]
+    fn sine_peaks_at_full_scale() {
+        let samples = render_waveform(Waveform::SINE, AntiAliasing::None, 1000f32, 48000);
+
+        assert!(
+            (peak(&samples) - 1f32).abs() < 1e-3,
+            "peak {}",
+            peak(&samples)
+        );
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn normalized_waveforms_have_equal_rms() {
+        let waveforms = [
+            Waveform::SINE,
+            Waveform::SAWTOOTH,
+            Waveform::TRIANGLE,
+            Waveform::SQUARE,
+            Waveform::PULSE,
+        ];
+
+        for anti_aliasing in anti_aliasing_modes() {
+            for waveform in waveforms {
+                let gain = waveform.normalization(anti_aliasing, 0.5);
+                let samples = render_waveform(waveform, anti_aliasing, 440f32, 48000);
+                let level = gain * rms(&samples);
+
+                assert!(
+                    (level / Waveform::REFERENCE_RMS - 1f32).abs() < 0.03,
+                    "{} {}: RMS {}",
+                    waveform,
+                    anti_aliasing,
+                    level
+                );
+                assert!(
+                    gain <= 1f32,
+                    "{} {}: gain {}",
+                    waveform,
+                    anti_aliasing,
+                    gain
+                );
+            }
+        }
+    }
+
+    /// A sawtooth with every partial below Nyquist and none above, in phase
+    /// with `sawtooth_naive`.
+    fn ideal_sawtooth(frequency: f32, n: usize) -> Vec<f32> {
+        let partials = (SAMPLE_RATE / 2f32 / frequency) as u32;
+
+        (1..=n)
+            .map(|clock| {
+                let phase = frequency * clock as f32 / SAMPLE_RATE;
+                let sum: f32 = (1..=partials)
+                    .map(|k| (2f32 * PI * k as f32 * phase).sin() / k as f32)
+                    .sum();
+                2f32 / PI * sum
+            })
+            .collect()
+    }
+
+    #[test]
+    fn polyblep_sawtooth_is_closer_to_band_limited_than_naive() {
+        let ideal = ideal_sawtooth(1000f32, 4800);
+        let error = |anti_aliasing| {
+            let samples = render_waveform(Waveform::SAWTOOTH, anti_aliasing, 1000f32, 4800);
+            let difference: Vec<f32> = samples.iter().zip(&ideal).map(|(a, b)| a - b).collect();
+            rms(&difference)
+        };
+        let (polyblep, naive) = (error(AntiAliasing::PolyBlep), error(AntiAliasing::None));
+
+        assert!(
+            polyblep < 0.5 * naive,
+            "PolyBLEP {} vs naive {}",
+            polyblep,
+            naive
+        );
+    }
+
+    /// A request at 1 kHz modulated up to 4 kHz must drop the partials that
+    /// the modulation pushes past Nyquist, just like a request at 4 kHz.
+    #[cfg(feature = "band-limited")]
+    #[test]
+    fn adaptive_harmonics_follow_frequency_modulation() {
+        let request = |frequency| {
+            WaveformRequest::new(frequency, 0f32, SAMPLE_RATE)
+                .with_anti_aliasing(AntiAliasing::Additive)
+                .with_max_harmonics(100)
+                .with_adaptive_harmonics(true)
+        };
+        let mut modulated = request(1000f32)
+            .with_frequency_modulation(Box::new(|| 4f32))
+            .sawtooth();
+        let mut direct = request(4000f32).sawtooth();
+
+        for (a, b) in render_samples(&mut modulated, 480)
+            .iter()
+            .zip(render_samples(&mut direct, 480))
+        {
+            assert!((a - b).abs() < 1e-3, "{} vs {}", a, b);
+        }
+    }
+
+    /// Past the few terms below Nyquist, raising --max-harmonics changes
+    /// nothing, even beyond where the squared term overflowed a u32.
+    #[cfg(feature = "band-limited")]
+    #[test]
+    fn additive_triangle_stops_at_nyquist() {
+        let triangle = |max_harmonics| {
+            let mut triangle = WaveformRequest::new(440f32, 0f32, SAMPLE_RATE)
+                .with_anti_aliasing(AntiAliasing::Additive)
+                .with_max_harmonics(max_harmonics)
+                .triangle();
+            render_samples(&mut triangle, 480)
+        };
+
+        assert_eq!(triangle(9), triangle(70000));
+        assert_eq!(triangle(9), triangle(u32::MAX));
+    }
+
+    #[test]
+    fn fm_without_modulation_is_a_sine() {
+        let request = || WaveformRequest::new(440f32, 0f32, SAMPLE_RATE);
+        let mut fm = request().fm(2f32, 0f32);
+        let mut sine = request().sine();
+
+        assert_eq!(
+            render_samples(&mut fm, 4800),
+            render_samples(&mut sine, 4800)
+        );
+    }
+
+    #[test]
+    fn soft_clip_stays_within_full_scale() {
+        for sample in [0.5, 1f32, 2f32, 10f32, 1e6, f32::MAX] {
+            for sample in [sample, -sample] {
+                let clipped = soft_clip(sample);
+
+                assert!(clipped.abs() <= 1f32, "{} became {}", sample, clipped);
+                assert_eq!(clipped.signum(), sample.signum());
+            }
+        }
+    }
+
+    #[test]
+    fn square_is_symmetric() {
+        for anti_aliasing in anti_aliasing_modes() {
+            // 100 Hz divides 48 kHz, so this is exactly 100 whole periods.
+            let samples = render_waveform(Waveform::SQUARE, anti_aliasing, 100f32, 48000);
+            let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+            let max = samples.iter().cloned().fold(f32::MIN, f32::max);
+            let min = samples.iter().cloned().fold(f32::MAX, f32::min);
+
+            assert!(mean.abs() < 1e-3, "{}: mean {}", anti_aliasing, mean);
+            assert!(
+                (max + min).abs() < 1e-2,
+                "{}: {} to {}",
+                anti_aliasing,
+                min,
+                max
+            );
+        }
+    }
+
+    /// The additive sawtooth ripples (Gibbs), so only the naive and PolyBLEP
+    /// ramps are checked. The last sample of the period is the reset, and
+    /// PolyBLEP rounds off the sample on either side of it.
+    #[test]
+    fn sawtooth_ramps_down_within_a_period() {
+        for anti_aliasing in [AntiAliasing::PolyBlep, AntiAliasing::None] {
+            let samples = render_waveform(Waveform::SAWTOOTH, anti_aliasing, 100f32, 480);
+
+            for pair in samples[1..478].windows(2) {
+                assert!(pair[1] < pair[0], "{}: {:?}", anti_aliasing, pair);
+            }
+        }
+    }
+}
@@ -1,166 +1,805 @@
+mod config;
+mod preset;
+
 use {
-    clap::Parser,
+    clap::{CommandFactory, FromArgMatches, Parser},
+    config::Config,
     cpal::traits::{DeviceTrait, HostTrait, StreamTrait},
-    std::f32::consts::PI,
-    std::fmt,
-    std::fmt::Display,
-    std::str::FromStr,
-    std::u32::MAX,
+    gen_waveform::{
+        arp::{self, ArpMode},
+        export, frame,
+        lfo::ModTarget,
+        noise, notes, raw,
+        score::{self, ScoreEvent},
+        sweep::{SweepMode, SweepRange},
+        tcp::SampleStream,
+        wavetable::Wavetable,
+        Alignment, AntiAliasing, Dtmf, Oscillator, Waveform,
+    },
+    preset::Preset,
+    std::sync::atomic::{AtomicUsize, Ordering},
+    std::sync::Arc,
 };
 
-#[derive(Parser, Debug)]
-#[clap(about, version, author)]
+/// Sample rate used when rendering without an output device.
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+#[derive(Parser, Debug, Clone)]
+#[clap(
+    about,
+    version,
+    author,
+    after_help = "Options marked [env: ...] can also be set through that environment \
+                  variable, e.g. GEN_WAVEFORM_FREQUENCY=220 or GEN_WAVEFORM_SOFT_CLIP=1. \
+                  The command line takes precedence."
+)]
 struct Args {
-    #[clap(short, long, default_value_t = Waveform::SINE)]
+    #[clap(short, long, default_value_t = Waveform::SINE, env = "GEN_WAVEFORM_WAVEFORM")]
     waveform: Waveform,
 
-    #[clap(short, long, default_value_t = 440)]
+    #[clap(short, long, default_value_t = 440, env = "GEN_WAVEFORM_FREQUENCY")]
     frequency: u32,
 
-    #[clap(short, long, default_value_t = 1)]
+    #[clap(short, long, default_value_t = 1, env = "GEN_WAVEFORM_TIME")]
     time: u64,
-}
 
-#[derive(Debug, Copy, Clone)]
-enum Waveform {
-    SINE,
-    SAWTOOTH,
-    TRIANGLE,
-    SQUARE,
-    NOISE,
+    /// Output level from 0.0 to 1.0
+    #[clap(short, long, default_value_t = 1f32, env = "GEN_WAVEFORM_VOLUME")]
+    volume: f32,
+
+    /// Output level in dBFS instead of --volume, e.g. -6, where 0 dBFS is a
+    /// sine peaking at full scale. The waveforms are normalized to about
+    /// -1.76 dBFS, and higher levels are capped there. Whichever of --volume
+    /// and --gain-db comes last on the command line wins
+    #[clap(long, allow_hyphen_values = true, overrides_with = "volume")]
+    gain_db: Option<f32>,
+
+    /// Comma-separated frequencies in Hz to play together as a chord of the
+    /// selected waveform, e.g. 261.63,329.63,392.0. Overrides --frequency
+    #[clap(
+        long,
+        use_value_delimiter = true,
+        conflicts_with_all = &["dtmf", "alignment", "staircase", "source-file"]
+    )]
+    chord: Vec<f32>,
+
+    /// Play a two-operator FM voice instead of a waveform: a sine carrier whose
+    /// phase is offset by a sine modulator
+    #[clap(long, conflicts_with_all = &["dtmf", "alignment", "staircase", "source-file"])]
+    fm: bool,
+
+    /// FM modulator frequency as a multiple of the carrier frequency
+    #[clap(long, default_value_t = 1f32, env = "GEN_WAVEFORM_FM_RATIO")]
+    fm_ratio: f32,
+
+    /// FM modulation index: peak phase deviation of the carrier in radians
+    #[clap(long, default_value_t = 1f32, env = "GEN_WAVEFORM_FM_INDEX")]
+    fm_index: f32,
+
+    /// Number of detuned copies of each voice to stack for a thicker sound
+    #[clap(long, default_value_t = 1, env = "GEN_WAVEFORM_UNISON")]
+    unison: u8,
+
+    /// Spread in cents between the lowest and highest --unison copies
+    #[clap(long, default_value_t = 20f32, env = "GEN_WAVEFORM_DETUNE")]
+    detune: f32,
+
+    /// Level of a sine sub-oscillator mixed under each voice, relative to the
+    /// voice itself. 0 leaves it out
+    #[clap(long, default_value_t = 0f32, env = "GEN_WAVEFORM_SUB_LEVEL")]
+    sub_level: f32,
+
+    /// How many octaves below the voice the sub-oscillator plays: 1 or 2
+    #[clap(
+        long,
+        default_value_t = 1,
+        possible_values = &["1", "2"],
+        env = "GEN_WAVEFORM_SUB_OCTAVE"
+    )]
+    sub_octave: u8,
+
+    /// Ring-modulate each voice: multiply it by a sine at --ring-freq for a
+    /// metallic, bell-like tone
+    #[clap(long, env = "GEN_WAVEFORM_RING_MOD")]
+    ring_mod: bool,
+
+    /// Frequency in Hz of the --ring-mod sine
+    #[clap(long, default_value_t = 100f32, env = "GEN_WAVEFORM_RING_FREQ")]
+    ring_freq: f32,
+
+    /// Low-pass filter cutoff in Hz. Without it, or at or above Nyquist, the
+    /// signal is unfiltered
+    #[clap(long, env = "GEN_WAVEFORM_CUTOFF")]
+    cutoff: Option<f32>,
+
+    /// Low-pass filter resonance from 0.0 (none) to 1.0 (sharp peak)
+    #[clap(long, default_value_t = 0f32, env = "GEN_WAVEFORM_RESONANCE")]
+    resonance: f32,
+
+    /// Echo delay in milliseconds. 0 leaves the delay out
+    #[clap(long, default_value_t = 0f32, env = "GEN_WAVEFORM_DELAY_MS")]
+    delay_ms: f32,
+
+    /// Fraction of each echo fed back into the delay, below 1.0
+    #[clap(long, default_value_t = 0.3, env = "GEN_WAVEFORM_DELAY_FEEDBACK")]
+    delay_feedback: f32,
+
+    /// Blend of echoes into the output from 0.0 (dry only) to 1.0 (echoes only)
+    #[clap(long, default_value_t = 0.5, env = "GEN_WAVEFORM_DELAY_MIX")]
+    delay_mix: f32,
+
+    /// Add a Freeverb-style reverb after the delay
+    #[clap(long, env = "GEN_WAVEFORM_REVERB")]
+    reverb: bool,
+
+    /// Reverb room size from 0.0 (short tail) to 1.0 (long tail)
+    #[clap(long, default_value_t = 0.5, env = "GEN_WAVEFORM_REVERB_ROOM")]
+    reverb_room: f32,
+
+    /// Blend of --reverb into the output from 0.0 (dry only) to 1.0 (reverb only)
+    #[clap(long, default_value_t = 0.3, env = "GEN_WAVEFORM_REVERB_WET")]
+    reverb_wet: f32,
+
+    /// Saturate the output with tanh so overshoot and summed voices stay
+    /// within ±1.0 instead of clipping hard. Also lowers loud signals
+    #[clap(long, env = "GEN_WAVEFORM_SOFT_CLIP")]
+    soft_clip: bool,
+
+    /// What the LFO modulates: freq (vibrato), volume (tremolo) or none
+    #[clap(long, default_value_t = ModTarget::None, env = "GEN_WAVEFORM_LFO_TARGET")]
+    lfo_target: ModTarget,
+
+    /// LFO rate in Hz
+    #[clap(long, default_value_t = 5f32, env = "GEN_WAVEFORM_LFO_RATE")]
+    lfo_rate: f32,
+
+    /// LFO depth from 0.0 to 1.0: octaves of vibrato, or the fraction the
+    /// volume dips for tremolo
+    #[clap(long, default_value_t = 0.1, env = "GEN_WAVEFORM_LFO_DEPTH")]
+    lfo_depth: f32,
+
+    /// LFO shape
+    #[clap(long, default_value_t = Waveform::SINE, env = "GEN_WAVEFORM_LFO_WAVEFORM")]
+    lfo_waveform: Waveform,
+
+    /// Envelope attack time in seconds
+    #[clap(long, default_value_t = 0f32, env = "GEN_WAVEFORM_ATTACK")]
+    attack: f32,
+
+    /// Envelope decay time in seconds
+    #[clap(long, default_value_t = 0f32, env = "GEN_WAVEFORM_DECAY")]
+    decay: f32,
+
+    /// Envelope sustain level from 0.0 to 1.0, held until --time elapses
+    #[clap(long, default_value_t = 1f32, env = "GEN_WAVEFORM_SUSTAIN")]
+    sustain: f32,
+
+    /// Envelope release time in seconds, played after --time. Never shorter
+    /// than --fade-ms
+    #[clap(long, default_value_t = 0f32, env = "GEN_WAVEFORM_RELEASE")]
+    release: f32,
+
+    /// Equal-power stereo position from -1.0 (left) to 1.0 (right). Without
+    /// it both channels play at full level; ignored on mono devices
+    #[clap(long, allow_hyphen_values = true, env = "GEN_WAVEFORM_PAN")]
+    pan: Option<f32>,
+
+    /// Length in milliseconds of the fade in when playback starts and of the
+    /// shortest release, so the output does not jump from or to silence
+    #[clap(long, default_value_t = 5f32, env = "GEN_WAVEFORM_FADE_MS")]
+    fade_ms: f32,
+
+    /// Output device to play on: an index from --list-devices or a
+    /// case-insensitive part of its name. Falls back to the default device
+    #[clap(short, long, env = "GEN_WAVEFORM_DEVICE")]
+    device: Option<String>,
+
+    /// Sample rate in Hz. Playback checks it against the device's supported
+    /// configs; exports and raw streams default to 44100 without it
+    #[clap(long, env = "GEN_WAVEFORM_SAMPLE_RATE")]
+    sample_rate: Option<u32>,
+
+    /// Number of output channels to open instead of the device default, if
+    /// the device supports it. Left and right go to the first two channels
+    /// and further channels repeat the left
+    #[clap(long)]
+    channels: Option<u16>,
+
+    /// Open a single output channel, summing left and right. Same as
+    /// --channels 1
+    #[clap(long, conflicts_with = "channels")]
+    mono: bool,
+
+    /// Fixed output buffer size in frames instead of the device default
+    #[clap(long, env = "GEN_WAVEFORM_BUFFER_SIZE")]
+    buffer_size: Option<u32>,
+
+    /// Suppress informational messages such as the output device, config,
+    /// frequency and volume. Warnings and errors still go to stderr
+    #[clap(short, long, env = "GEN_WAVEFORM_QUIET")]
+    quiet: bool,
+
+    /// Print the available output devices with their indices and exit
+    #[clap(long)]
+    list_devices: bool,
+
+    /// Write --time seconds to this 16-bit mono WAV file instead of playing
+    #[clap(short, long)]
+    output: Option<std::path::PathBuf>,
+
+    /// Play one cycle loaded from this WAV or CSV file as the waveform,
+    /// normalized to ±1.0. Selects the wavetable waveform
+    #[clap(long)]
+    wavetable: Option<std::path::PathBuf>,
+
+    /// The cycle of --wavetable, loaded before playback.
+    #[clap(skip)]
+    table: Option<Wavetable>,
+
+    /// Fraction of each cycle the pulse waveform spends high, from 0.01 to 0.99
+    #[clap(long, default_value_t = 0.5, env = "GEN_WAVEFORM_DUTY_CYCLE")]
+    duty_cycle: f32,
+
+    /// Color of the noise waveform: white, pink, brown or blue, or a spectral
+    /// tilt from -1 to 1 where 0 is white, -0.5 pink, -1 brown and 1 blue.
+    /// Every color is as loud as white, so colored noise peaks well past full
+    /// scale; lower the level with --gain-db -9 or so to keep it from clipping
+    #[clap(
+        long,
+        default_value_t = 0f32,
+        allow_hyphen_values = true,
+        parse(try_from_str = noise::parse_color),
+        env = "GEN_WAVEFORM_NOISE_COLOR"
+    )]
+    noise_color: f32,
+
+    /// Seed for the noise waveform and noise LFO so that renders are
+    /// reproducible. Without it every run is different
+    #[clap(long, env = "GEN_WAVEFORM_SEED")]
+    seed: Option<u64>,
+
+    /// How sawtooth, square and pulse avoid aliasing: additive (band-limited
+    /// builds only), polyblep or none. Defaults to polyblep in band-limited
+    /// builds and none otherwise
+    #[clap(long, default_value_t, env = "GEN_WAVEFORM_ANTI_ALIASING")]
+    anti_aliasing: AntiAliasing,
+
+    /// Highest harmonic summed by the additive sawtooth, square and pulse.
+    /// Low notes have room for many more partials below Nyquist, so they sound
+    /// duller than the ideal shape; raise this to brighten them at a CPU cost.
+    /// The additive triangle sums its odd terms n up to this, each at n^2
+    /// times the frequency, and always stops below Nyquist
+    #[cfg(feature = "band-limited")]
+    #[clap(long, default_value_t = 49, env = "GEN_WAVEFORM_MAX_HARMONICS")]
+    max_harmonics: u32,
+
+    /// Skip additive sawtooth, square and pulse partials at or above Nyquist,
+    /// as the triangle always does. High notes then evaluate far fewer terms
+    /// per sample; --max-harmonics still caps low notes, trading their
+    /// brightness for CPU
+    #[cfg(feature = "band-limited")]
+    #[clap(long, env = "GEN_WAVEFORM_ADAPTIVE_HARMONICS")]
+    adaptive_harmonics: bool,
+
+    /// Play the DTMF tone pair for a keypad digit (0-9, *, #, A-D) instead of a waveform
+    #[clap(long)]
+    dtmf: Option<Dtmf>,
+
+    /// Play a broadcast alignment tone instead of a waveform: ebu is a 1 kHz
+    /// sine at -18 dBFS (EBU R68), smpte is a 1 kHz sine at -20 dBFS (SMPTE RP155)
+    #[clap(long, conflicts_with = "dtmf")]
+    alignment: Option<Alignment>,
+
+    /// Play a staircase of this many DC levels stepping from -1 to 1, for
+    /// checking DAC linearity
+    #[clap(long, conflicts_with_all = &["dtmf", "alignment"])]
+    staircase: Option<u32>,
+
+    /// Play the right channel this many Hz above the left to produce a
+    /// binaural beat, e.g. 4.5. Needs a stereo output, so --output and --csv,
+    /// which write mono, reject it
+    #[clap(
+        long,
+        parse(try_from_str = parse_beat),
+        conflicts_with_all = &["dtmf", "alignment", "staircase", "output", "csv"]
+    )]
+    binaural_beat: Option<f32>,
+
+    /// Loop a WAV file instead of synthesizing. Stereo files keep their left
+    /// and right channels; mono output sums them
+    #[clap(long, conflicts_with_all = &["dtmf", "alignment", "staircase", "binaural-beat"])]
+    source_file: Option<std::path::PathBuf>,
+
+    /// Play a score of `note duration` lines, e.g. `A4 0.5` or `rest 0.25`,
+    /// retriggering the envelope for every note. Replaces --time
+    #[clap(
+        long = "score",
+        conflicts_with_all = &[
+            "dtmf",
+            "alignment",
+            "staircase",
+            "source-file",
+            "chord",
+            "binaural-beat",
+        ]
+    )]
+    score_file: Option<std::path::PathBuf>,
+
+    /// The notes of --score, loaded before playback.
+    #[clap(skip)]
+    score: Vec<ScoreEvent>,
+
+    /// Sweep the frequency from start to end Hz over a number of seconds,
+    /// e.g. 20:20000:10, keeping the phase continuous. Replaces --frequency
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "dtmf",
+            "alignment",
+            "staircase",
+            "source-file",
+            "chord",
+            "score-file",
+            "binaural-beat",
+        ]
+    )]
+    sweep: Option<SweepRange>,
+
+    /// How --sweep moves between frequencies: lin (equal Hz per second) or log
+    /// (equal octaves per second)
+    #[clap(long, default_value_t = SweepMode::Logarithmic, env = "GEN_WAVEFORM_SWEEP_MODE")]
+    sweep_mode: SweepMode,
+
+    /// Hold the end frequency of --sweep until --time elapses instead of
+    /// stopping when the sweep ends
+    #[clap(long, env = "GEN_WAVEFORM_SWEEP_HOLD")]
+    sweep_hold: bool,
+
+    /// Comma-separated frequencies in Hz to arpeggiate for --time seconds,
+    /// retriggering the envelope on every step, e.g. 261.63,329.63,392.0
+    #[clap(
+        long,
+        use_value_delimiter = true,
+        conflicts_with_all = &[
+            "dtmf",
+            "alignment",
+            "staircase",
+            "source-file",
+            "chord",
+            "score-file",
+            "sweep",
+            "binaural-beat",
+        ]
+    )]
+    arp: Vec<f32>,
+
+    /// --arp steps per second
+    #[clap(long, default_value_t = 8f32, env = "GEN_WAVEFORM_ARP_RATE")]
+    arp_rate: f32,
+
+    /// --arp order: up, down or updown
+    #[clap(long, default_value_t = ArpMode::Up, env = "GEN_WAVEFORM_ARP_MODE")]
+    arp_mode: ArpMode,
+
+    /// Seconds each --staircase level is held
+    #[clap(long, default_value_t = 0.5, env = "GEN_WAVEFORM_STEP_DURATION")]
+    step_duration: f32,
+
+    /// Debug: log every sample-to-sample jump larger than this threshold to
+    /// stderr, to catch click-inducing bugs
+    #[clap(long, env = "GEN_WAVEFORM_DETECT_CLICKS")]
+    detect_clicks: Option<f32>,
+
+    /// Play the tone on each output channel in turn, for --time seconds each
+    #[clap(long)]
+    channel_test: bool,
+
+    /// Comma-separated gain in dB for each output channel, e.g. 0,-3,-6.
+    /// Channels without an entry play at 0 dB
+    #[clap(
+        long,
+        use_value_delimiter = true,
+        allow_hyphen_values = true,
+        env = "GEN_WAVEFORM_CHANNEL_GAINS"
+    )]
+    channel_gains: Vec<f32>,
+
+    /// Stream mono little-endian PCM to stdout in real time instead of playing
+    /// on a device, at --sample-rate (44100 Hz by default) for --time seconds
+    #[clap(long)]
+    raw_stdout: bool,
+
+    /// Print --time seconds of mono samples to stdout as index,time,amplitude
+    /// CSV lines instead of playing, at --sample-rate (44100 Hz by default)
+    #[clap(long, conflicts_with_all = &["raw-stdout", "output"])]
+    csv: bool,
+
+    /// Load waveform, frequency, volume, envelope, filter, pan and LFO
+    /// settings from a TOML preset, replacing those given on the command line
+    #[clap(long)]
+    load_preset: Option<std::path::PathBuf>,
+
+    /// Save the waveform, frequency, volume, envelope, filter, pan and LFO
+    /// settings to a TOML preset before playing
+    #[clap(long)]
+    save_preset: Option<std::path::PathBuf>,
+
+    /// Read default waveform, frequency, volume, sample rate and device from
+    /// this TOML file instead of ~/.config/gen-waveform/config.toml. Flags
+    /// given on the command line override it
+    #[clap(long)]
+    config: Option<std::path::PathBuf>,
+
+    /// Ignore the config file
+    #[clap(long, conflicts_with = "config")]
+    no_config: bool,
+
+    /// Serve the mono output to TCP clients on this address, e.g.
+    /// 127.0.0.1:9000, as lines of JSON sample arrays about ten times a second
+    #[clap(long)]
+    stream: Option<String>,
+
+    /// Sample encoding for --raw-stdout: f32 or i16
+    #[clap(long, default_value_t = raw::RawFormat::F32, env = "GEN_WAVEFORM_RAW_FORMAT")]
+    raw_format: raw::RawFormat,
 }
 
-impl FromStr for Waveform {
-    type Err = anyhow::Error;
+impl Args {
+    /// Parses the command line, with the GEN_WAVEFORM_* variables filling in
+    /// the options it leaves out and the values of the config file in place of
+    /// the built-in defaults.
+    fn parse_with_config() -> Result<Self, anyhow::Error> {
+        let args = Args::parse();
+        if args.no_config {
+            return Ok(args);
+        }
 
-    fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
-        match s {
-            "sine" => Ok(Waveform::SINE),
-            "sin" => Ok(Waveform::SINE),
-            "sawtooth" => Ok(Waveform::SAWTOOTH),
-            "saw" => Ok(Waveform::SAWTOOTH),
-            "triangle" => Ok(Waveform::TRIANGLE),
-            "tri" => Ok(Waveform::TRIANGLE),
-            "square" => Ok(Waveform::SQUARE),
-            "squ" => Ok(Waveform::SQUARE),
-            "noise" => Ok(Waveform::NOISE),
-            "noi" => Ok(Waveform::NOISE),
-            _ => Err(anyhow::anyhow!("Unknown waveform")),
+        let config = match (&args.config, Config::default_path()) {
+            (Some(path), _) => Config::load(path)?,
+            (None, Some(path)) if path.exists() => Config::load(path)?,
+            (None, _) => return Ok(args),
+        };
+        let defaults = config.defaults();
+        let mut command = Args::command();
+        for (id, value) in &defaults {
+            command = command.mut_arg(*id, |arg| arg.default_value(value));
         }
+
+        Ok(Args::from_arg_matches(&command.get_matches()).unwrap_or_else(|err| err.exit()))
     }
-}
 
-impl Display for Waveform {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let s = match &self {
-            Waveform::SINE => "sine",
-            Waveform::SAWTOOTH => "sawtooth",
-            Waveform::TRIANGLE => "triangle",
-            Waveform::SQUARE => "square",
-            Waveform::NOISE => "noise",
-        };
+    /// The channel count asked for with --channels or --mono, if any.
+    fn requested_channels(&self) -> Option<u16> {
+        if self.mono {
+            Some(1)
+        } else {
+            self.channels
+        }
+    }
 
-        write!(f, "{}", s)
+    /// The sound described by the command line, without the output routing.
+    fn oscillator(&self) -> Oscillator {
+        Oscillator {
+            waveform: self.waveform,
+            frequency: self.frequency as f32,
+            time: std::time::Duration::from_secs(self.time),
+            volume: self.volume,
+            chord: self.chord.clone(),
+            fm: self.fm,
+            fm_ratio: self.fm_ratio,
+            fm_index: self.fm_index,
+            unison: self.unison,
+            detune: self.detune,
+            sub_level: self.sub_level,
+            sub_octave: self.sub_octave,
+            ring_mod: self.ring_mod,
+            ring_freq: self.ring_freq,
+            cutoff: self.cutoff,
+            resonance: self.resonance,
+            delay_ms: self.delay_ms,
+            delay_feedback: self.delay_feedback,
+            delay_mix: self.delay_mix,
+            reverb: self.reverb,
+            reverb_room: self.reverb_room,
+            reverb_wet: self.reverb_wet,
+            soft_clip: self.soft_clip,
+            lfo_target: self.lfo_target,
+            lfo_rate: self.lfo_rate,
+            lfo_depth: self.lfo_depth,
+            lfo_waveform: self.lfo_waveform,
+            attack: self.attack,
+            decay: self.decay,
+            sustain: self.sustain,
+            release: self.release,
+            fade_ms: self.fade_ms,
+            table: self.table.clone(),
+            duty_cycle: self.duty_cycle,
+            noise_color: self.noise_color,
+            seed: self.seed,
+            anti_aliasing: self.anti_aliasing,
+            #[cfg(feature = "band-limited")]
+            max_harmonics: self.max_harmonics,
+            #[cfg(feature = "band-limited")]
+            adaptive_harmonics: self.adaptive_harmonics,
+            dtmf: self.dtmf,
+            alignment: self.alignment,
+            staircase: self.staircase,
+            step_duration: self.step_duration,
+            source_file: self.source_file.clone(),
+            score: self.score.clone(),
+            sweep: self.sweep,
+            sweep_mode: self.sweep_mode,
+            sweep_hold: self.sweep_hold,
+            detect_clicks: self.detect_clicks,
+            binaural_beat: self.binaural_beat,
+        }
     }
 }
 
-struct WaveformRequest {
-    frequency: f32,
-    sample_clock: f32,
-    sample_rate: f32,
+/// Parses --binaural-beat: a positive, finite offset in Hz.
+fn parse_beat(s: &str) -> Result<f32, anyhow::Error> {
+    match s.parse::<f32>() {
+        Ok(beat) if beat.is_finite() && beat > 0f32 => Ok(beat),
+        _ => Err(anyhow::anyhow!(
+            "Binaural beat must be a positive number of Hz"
+        )),
+    }
 }
 
-impl WaveformRequest {
-    fn new(frequency: f32, sample_clock: f32, sample_rate: f32) -> Self {
-        Self {
-            frequency,
-            sample_clock,
-            sample_rate,
+fn main() -> Result<(), anyhow::Error> {
+    let mut args = Args::parse_with_config()?;
+
+    if let Some(db) = args.gain_db {
+        let normalization_db = args.oscillator().normalization_db();
+        args.volume = 10f32.powf((db - normalization_db) / 20f32).min(1f32);
+    }
+
+    if let Some(path) = &args.load_preset {
+        Preset::load(path)?.apply(&mut args)?;
+    }
+    if let Some(path) = &args.score_file {
+        args.score = score::load(path)?;
+    }
+    if let Some(path) = &args.wavetable {
+        args.table = Some(Wavetable::load(path)?);
+        args.waveform = Waveform::WAVETABLE;
+    }
+    if let (Waveform::WAVETABLE, None) = (args.waveform, &args.table) {
+        return Err(anyhow::anyhow!("the wavetable waveform needs --wavetable"));
+    }
+    if !args.arp.is_empty() {
+        args.score = arp::arpeggio(&args.arp, args.arp_mode, args.arp_rate, args.time as f32);
+    }
+    if let Some(path) = &args.save_preset {
+        Preset::from_args(&args).save(path)?;
+        if !args.quiet {
+            eprintln!("Saved preset to {}", path.display());
         }
     }
 
-    fn base_waveform(&mut self, value: f32, frequency: f32, sample_rate: f32) -> f32 {
-        (2f32 * PI * frequency * self.sample_clock * value / self.sample_rate + sample_rate).sin()
+    let oscillator = args.oscillator();
+
+    if args.raw_stdout {
+        if args.binaural_beat.is_some() {
+            eprintln!("warning: --binaural-beat has no effect on mono --raw-stdout output");
+        }
+        let sample_rate = args.sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE);
+        let mut waveform_fn = oscillator.samples(sample_rate as f32)?;
+        return raw::stream(
+            &mut waveform_fn,
+            args.raw_format,
+            sample_rate,
+            oscillator.duration(),
+        );
     }
 
-    fn tick(&mut self) {
-        self.sample_clock = (self.sample_clock + 1f32) % self.sample_rate;
+    if args.csv {
+        let sample_rate = args.sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE);
+        let mut waveform_fn = oscillator.samples(sample_rate as f32)?;
+        return export::write_csv(&mut waveform_fn, sample_rate, oscillator.duration());
     }
 
-    fn sine(mut self) -> Box<dyn FnMut() -> f32 + Send> {
-        Box::new(move || {
-            self.tick();
-            self.base_waveform(1f32, self.frequency, 0f32)
-        })
+    let host = cpal::default_host();
+
+    if args.list_devices {
+        for (index, device) in host.output_devices()?.enumerate() {
+            println!("{}: {}", index, device.name()?);
+        }
+
+        return Ok(());
     }
 
-    fn sawtooth(mut self) -> Box<dyn FnMut() -> f32 + Send> {
-        Box::new(move || {
-            self.tick();
-            let mut result = 0f32;
+    if let Some(path) = &args.output {
+        let sample_rate = args.sample_rate.unwrap_or_else(|| {
+            host.default_output_device()
+                .and_then(|device| device.default_output_config().ok())
+                .map_or(DEFAULT_SAMPLE_RATE, |config| config.sample_rate().0)
+        });
+        let mut waveform_fn = oscillator.samples(sample_rate as f32)?;
+        export::write_wav(path, &mut waveform_fn, sample_rate, oscillator.duration())?;
+        if !args.quiet {
+            println!(
+                "Wrote {:?} at {} Hz to {}",
+                oscillator.duration(),
+                sample_rate,
+                path.display()
+            );
+        }
 
-            for n in 1..50 {
-                result += 1f32 / n as f32 * self.base_waveform(n as f32, self.frequency, 0f32);
-            }
+        return Ok(());
+    }
 
-            result
-        })
+    let output_device = select_output_device(&host, args.device.as_deref())?
+        .or_else(|| host.default_output_device())
+        .expect("failed to find a default output device");
+    let config = output_device.default_output_config()?;
+    if !args.quiet {
+        println!("Output device: {}", output_device.name()?);
+        println!("Default output config: {:?}", config);
     }
 
-    fn square(mut self) -> Box<dyn FnMut() -> f32 + Send> {
-        Box::new(move || {
-            self.tick();
-            let mut result = 0f32;
+    let (sample_format, config) = stream_config(&output_device, config, &args)?;
+    let requested = args.sample_rate.is_some()
+        || args.buffer_size.is_some()
+        || args.requested_channels().is_some();
+    if requested && !args.quiet {
+        println!("Requested output config: {:?}", config);
+    }
 
-            for n in (1..50).step_by(2) {
-                result += 1f32 / n as f32 * self.base_waveform(n as f32, self.frequency, 0f32);
-            }
+    match sample_format {
+        cpal::SampleFormat::F32 => run::<f32>(&output_device, &config, args),
+        cpal::SampleFormat::I16 => run::<i16>(&output_device, &config, args),
+        cpal::SampleFormat::U16 => run::<u16>(&output_device, &config, args),
+    }
+}
 
-            result
-        })
+/// Applies --sample-rate and --buffer-size to the device's default config,
+/// listing what the device supports when the request can't be met.
+fn stream_config(
+    device: &cpal::Device,
+    default: cpal::SupportedStreamConfig,
+    args: &Args,
+) -> Result<(cpal::SampleFormat, cpal::StreamConfig), anyhow::Error> {
+    let requested_channels = args.requested_channels();
+    if args.sample_rate.is_none() && args.buffer_size.is_none() && requested_channels.is_none() {
+        return Ok((default.sample_format(), default.into()));
     }
 
-    fn triangle(mut self) -> Box<dyn FnMut() -> f32 + Send> {
-        Box::new(move || {
-            self.tick();
-            let mut result = 0f32;
+    let all_ranges: Vec<_> = device.supported_output_configs()?.collect();
+    let channels = match requested_channels {
+        Some(channels) if all_ranges.iter().any(|range| range.channels() == channels) => channels,
+        Some(channels) => {
+            eprintln!(
+                "warning: the device does not support {} channels, using {}",
+                channels,
+                default.channels()
+            );
+            default.channels()
+        }
+        None => default.channels(),
+    };
+    let sample_rate = cpal::SampleRate(args.sample_rate.unwrap_or(default.sample_rate().0));
+    let buffer_fits =
+        |range: &cpal::SupportedStreamConfigRange| match (args.buffer_size, range.buffer_size()) {
+            (Some(size), cpal::SupportedBufferSize::Range { min, max }) => {
+                *min <= size && size <= *max
+            }
+            _ => true,
+        };
+    let ranges: Vec<_> = all_ranges
+        .into_iter()
+        .filter(|range| range.channels() == channels)
+        .collect();
+    let supported = ranges.iter().find(|range| {
+        range.min_sample_rate() <= sample_rate
+            && sample_rate <= range.max_sample_rate()
+            && buffer_fits(range)
+    });
+
+    match supported {
+        Some(range) => {
+            let supported = range.clone().with_sample_rate(sample_rate);
+            let mut config = supported.config();
+            if let Some(size) = args.buffer_size {
+                config.buffer_size = cpal::BufferSize::Fixed(size);
+            }
 
-            for n in (1..50 as i32).step_by(2) {
-                let p: f32 = n.pow(2) as f32;
-                result += 1f32 / p * self.base_waveform(p, self.frequency, 0f32);
+            Ok((supported.sample_format(), config))
+        }
+        None => {
+            eprintln!("Supported output configs:");
+            for range in &ranges {
+                eprintln!(
+                    "  {} channels, {}-{} Hz, {:?}, buffer {:?}",
+                    range.channels(),
+                    range.min_sample_rate().0,
+                    range.max_sample_rate().0,
+                    range.sample_format(),
+                    range.buffer_size()
+                );
             }
 
-            result
-        })
+            Err(anyhow::anyhow!(
+                "the device does not support {} Hz on {} channels{}",
+                sample_rate.0,
+                channels,
+                args.buffer_size.map_or(String::new(), |size| format!(
+                    " with a {} frame buffer",
+                    size
+                ))
+            ))
+        }
     }
+}
 
-    fn white_noise(mut self) -> Box<dyn FnMut() -> f32 + Send> {
-        Box::new(move || {
-            self.tick();
-            let seed = rand::random::<u32>();
-            let theta = seed as f32 / MAX as f32 * 2f32 * PI;
+/// Finds the output device at index `selector`, or otherwise the first whose
+/// name contains it, ignoring case. Warns and returns None when nothing matches.
+fn select_output_device(
+    host: &cpal::Host,
+    selector: Option<&str>,
+) -> Result<Option<cpal::Device>, anyhow::Error> {
+    let selector = match selector {
+        Some(selector) => selector,
+        None => return Ok(None),
+    };
+    let needle = selector.to_lowercase();
+    let device = match selector.parse::<usize>() {
+        Ok(index) => host.output_devices()?.nth(index),
+        Err(_) => host.output_devices()?.find(|device| {
+            device
+                .name()
+                .is_ok_and(|name| name.to_lowercase().contains(&needle))
+        }),
+    };
 
-            self.base_waveform(1f32, theta, theta)
-        })
+    if device.is_some() {
+        return Ok(device);
     }
+    eprintln!(
+        "warning: no output device matches \"{}\", using the default",
+        selector
+    );
+
+    Ok(None)
 }
 
-fn main() -> Result<(), anyhow::Error> {
-    let host = cpal::default_host();
-    let output_device = host
-        .default_output_device()
-        .expect("failed to find a default output device");
-    println!("Output device: {}", output_device.name()?);
+/// Prints the output level as a percentage and in dBFS, counting the
+/// `normalization_db` the source is played at.
+fn print_volume(volume: f32, normalization_db: f32) {
+    let volume = volume.clamp(0f32, 1f32);
 
-    let config = output_device.default_output_config()?;
-    println!("Default output config: {:?}", config);
+    println!(
+        "Volume: {:.0}% ({:.1} dBFS)",
+        volume * 100f32,
+        20f32 * volume.log10() + normalization_db
+    );
+}
 
-    let args = Args::parse();
+/// Prints the pitch of each voice with its nearest note name. Sources with a
+/// fixed or no pitch print nothing.
+fn print_frequencies(args: &Args) {
+    if args.dtmf.is_some()
+        || args.alignment.is_some()
+        || args.staircase.is_some()
+        || args.source_file.is_some()
+        || !args.score.is_empty()
+    {
+        return;
+    }
 
-    match config.sample_format() {
-        cpal::SampleFormat::F32 => run::<f32>(&output_device, &config.into(), args),
-        cpal::SampleFormat::I16 => run::<i16>(&output_device, &config.into(), args),
-        cpal::SampleFormat::U16 => run::<u16>(&output_device, &config.into(), args),
+    if let Some(range) = args.sweep {
+        println!(
+            "Sweep: {} to {} over {}s",
+            notes::describe(range.start),
+            notes::describe(range.end),
+            range.seconds
+        );
+    } else if args.chord.is_empty() {
+        println!("Frequency: {}", notes::describe(args.frequency as f32));
+    } else {
+        for frequency in &args.chord {
+            println!("Frequency: {}", notes::describe(*frequency));
+        }
     }
 }
 
@@ -173,36 +812,227 @@ where
     T: cpal::Sample,
 {
     let channels = config.channels as usize;
-    let waveform_req =
-        WaveformRequest::new(args.frequency as f32, 0f32, config.sample_rate.0 as f32);
-    let mut waveform_fn: Box<dyn FnMut() -> f32 + Send> = match args.waveform {
-        Waveform::SINE => waveform_req.sine(),
-        Waveform::SAWTOOTH => waveform_req.sawtooth(),
-        Waveform::TRIANGLE => waveform_req.triangle(),
-        Waveform::SQUARE => waveform_req.square(),
-        Waveform::NOISE => waveform_req.white_noise(),
-    };
+    let gains = channel_gains(&args.channel_gains, channels)?;
+    let mut oscillator = args.oscillator();
+    if args.channel_test {
+        // Every channel plays for --time, so hold the envelope across all of
+        // them instead of releasing it after the first.
+        oscillator.time *= channels as u32;
+    }
+    let mut frame_fn = oscillator.frames(config.sample_rate.0 as f32)?;
+    if args.binaural_beat.is_some() && channels < 2 {
+        eprintln!("warning: --binaural-beat needs two channels; the output device is mono");
+    }
+    if let (Some(pan), true) = (args.pan, channels >= 2) {
+        frame_fn = frame::panned(frame_fn, pan);
+    }
+    if let Some(address) = &args.stream {
+        let stream = SampleStream::serve(address, config.sample_rate.0)?;
+        if !args.quiet {
+            println!("Streaming samples on {}", address);
+        }
+        frame_fn = frame::streamed(frame_fn, stream);
+    }
+    let active_channel = Arc::new(AtomicUsize::new(0));
+    let callback_channel = Arc::clone(&active_channel);
+    let channel_test = args.channel_test;
     let err_fn = |err: cpal::StreamError| eprintln!("an error occurred on stream: {}", err);
-    let output_data_fn = move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-        write_data(data, channels, &mut waveform_fn)
+    let output_data_fn = move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+        let active = if channel_test {
+            Some(callback_channel.load(Ordering::Relaxed))
+        } else {
+            None
+        };
+        write_data(data, channels, &mut frame_fn, &gains, active)
     };
     let stream = output_device.build_output_stream(config, output_data_fn, err_fn)?;
+    if !args.quiet {
+        print_frequencies(&args);
+        print_volume(args.volume, oscillator.normalization_db());
+    }
+    let time = std::time::Duration::from_secs(args.time);
+    let duration = oscillator.duration();
 
     stream.play()?;
-    std::thread::sleep(std::time::Duration::from_secs(args.time));
+    if channel_test {
+        for channel in 0..channels {
+            active_channel.store(channel, Ordering::Relaxed);
+            if !args.quiet {
+                println!("Playing on channel {} of {}", channel + 1, channels);
+            }
+            std::thread::sleep(time);
+        }
+        std::thread::sleep(duration.saturating_sub(oscillator.gate()));
+    } else {
+        std::thread::sleep(duration);
+    }
     drop(stream);
 
     Ok(())
 }
 
-fn write_data<T, F>(output: &mut [T], channels: usize, next_sample: &mut F)
-where
+/// Converts per-channel dB gains to linear, padding missing channels with unity.
+fn channel_gains(gains_db: &[f32], channels: usize) -> Result<Vec<f32>, anyhow::Error> {
+    if gains_db.len() > channels {
+        return Err(anyhow::anyhow!(
+            "{} channel gains given but the device has {} channels",
+            gains_db.len(),
+            channels
+        ));
+    }
+
+    Ok((0..channels)
+        .map(|channel| {
+            gains_db
+                .get(channel)
+                .map_or(1f32, |db| 10f32.powf(db / 20f32))
+        })
+        .collect())
+}
+
+/// Writes (left, right) frames to the first two channels; any further
+/// channels repeat the left side and a single channel carries their sum.
+fn write_data<T, F>(
+    output: &mut [T],
+    channels: usize,
+    next_frame: &mut F,
+    gains: &[f32],
+    active_channel: Option<usize>,
+) where
     T: cpal::Sample,
-    F: FnMut() -> f32 + Send,
+    F: FnMut() -> (f32, f32) + Send,
 {
-    for i in (0..output.len()).step_by(channels) {
-        let value: T = cpal::Sample::from::<f32>(&next_sample());
-        output[i] = value;
-        output[i + 1] = value;
+    for frame in output.chunks_mut(channels) {
+        let (left, right) = next_frame();
+        let left = if channels == 1 {
+            0.5 * (left + right)
+        } else {
+            left
+        };
+
+        for (channel, sample) in frame.iter_mut().enumerate() {
+            let value = if channel == 1 { right } else { left };
+            let value = match active_channel {
+                Some(active) if active != channel => 0f32,
+                _ => value * gains[channel],
+            };
+            *sample = cpal::Sample::from::<f32>(&value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::sync::Mutex};
+
+    /// Parses `argv` without a config file, with `vars` set in the
+    /// environment. The variables are process-wide, so these parses take turns.
+    fn parse(argv: &[&str], vars: &[(&str, &str)]) -> Result<Args, clap::Error> {
+        static ENV: Mutex<()> = Mutex::new(());
+        let _env = ENV.lock().unwrap_or_else(|err| err.into_inner());
+
+        for (name, value) in vars {
+            std::env::set_var(name, value);
+        }
+        let args = Args::try_parse_from(["gen-waveform", "--no-config"].iter().chain(argv));
+        for (name, _) in vars {
+            std::env::remove_var(name);
+        }
+        args
+    }
+
+    #[test]
+    fn environment_fills_in_missing_options() {
+        let args = parse(
+            &[],
+            &[
+                ("GEN_WAVEFORM_WAVEFORM", "saw"),
+                ("GEN_WAVEFORM_FREQUENCY", "220"),
+                ("GEN_WAVEFORM_CHANNEL_GAINS", "0,-3"),
+                ("GEN_WAVEFORM_SOFT_CLIP", "1"),
+                ("GEN_WAVEFORM_RING_MOD", "false"),
+            ],
+        )
+        .unwrap();
+
+        assert!(matches!(args.waveform, Waveform::SAWTOOTH));
+        assert_eq!(args.frequency, 220);
+        assert_eq!(args.channel_gains, vec![0f32, -3f32]);
+        assert!(args.soft_clip);
+        assert!(!args.ring_mod);
+    }
+
+    #[test]
+    fn command_line_overrides_environment() {
+        let args = parse(&["-f", "880"], &[("GEN_WAVEFORM_FREQUENCY", "220")]).unwrap();
+
+        assert_eq!(args.frequency, 880);
+    }
+
+    #[test]
+    fn command_line_overrides_conflicting_environment() {
+        let args = parse(&["--gain-db", "-6"], &[("GEN_WAVEFORM_VOLUME", "0.5")]).unwrap();
+        assert_eq!(args.gain_db, Some(-6f32));
+
+        let args = parse(&["--volume", "0.25"], &[("GEN_WAVEFORM_VOLUME", "0.5")]).unwrap();
+        assert_eq!(args.volume, 0.25);
+        assert_eq!(args.gain_db, None);
+    }
+
+    #[test]
+    fn environment_sets_only_marked_options() {
+        let args = parse(
+            &[],
+            &[
+                ("GEN_WAVEFORM_HELP", "1"),
+                ("GEN_WAVEFORM_VERSION", "1"),
+                ("GEN_WAVEFORM_DTMF", "5"),
+                ("GEN_WAVEFORM_GAIN_DB", "-6"),
+            ],
+        )
+        .unwrap();
+
+        assert!(args.dtmf.is_none());
+        assert_eq!(args.gain_db, None);
+    }
+
+    #[test]
+    fn binaural_beat_takes_fractional_hz() {
+        let args = Args::try_parse_from(["gen-waveform", "--binaural-beat", "4.5"]).unwrap();
+        assert_eq!(args.binaural_beat, Some(4.5));
+
+        for beat in ["0", "inf", "1e40", "NaN"] {
+            assert!(
+                Args::try_parse_from(["gen-waveform", "--binaural-beat", beat]).is_err(),
+                "{}",
+                beat
+            );
+        }
+    }
+
+    #[test]
+    fn noise_color_takes_names_and_numbers_from_minus_one_to_one() {
+        for (color, expected) in [("pink", -0.5), ("-1", -1f32), ("0.25", 0.25), ("1", 1f32)] {
+            let args = Args::try_parse_from(["gen-waveform", "--noise-color", color]).unwrap();
+            assert_eq!(args.noise_color, expected);
+        }
+
+        for color in ["-1.5", "2", "NaN", "purple"] {
+            assert!(
+                Args::try_parse_from(["gen-waveform", "--noise-color", color]).is_err(),
+                "{}",
+                color
+            );
+        }
+    }
+
+    #[test]
+    fn binaural_beat_rejects_mono_exports() {
+        for export in [&["--output", "beat.wav"][..], &["--csv"]] {
+            let argv = ["gen-waveform", "--binaural-beat", "4"]
+                .iter()
+                .chain(export);
+            assert!(Args::try_parse_from(argv).is_err(), "{:?}", export);
+        }
     }
 }
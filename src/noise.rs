@@ -0,0 +1,100 @@
+use {
+    rand::{rngs::StdRng, Rng},
+    std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2},
+};
+
+/// Tilts white noise towards brown (negative colors) or blue (positive
+/// colors) by crossfading it with filtered copies of a uniform white source.
+/// -0.5 is pink, -1 is brown and 1 is blue. Every color is scaled to the RMS
+/// of the white noise, so changing it keeps the loudness. White noise peaks
+/// at 1.4 times its RMS, blue at 2.4 times and pink and brown at four to five
+/// times, so at full volume every color but white clips unless the volume is
+/// about 9 dB lower.
+pub struct NoiseColor {
+    color: f32,
+    rng: StdRng,
+    pink: [f32; 3],
+    brown: f32,
+    previous: f32,
+}
+
+impl NoiseColor {
+    /// RMS of the white noise: a sine at a random phase every sample.
+    const WHITE_RMS: f32 = FRAC_1_SQRT_2;
+    /// RMS of the filters below for uniform input in ±1, measured over 80
+    /// seconds. Blue is 0.5(u - u') for a variance of 2/3 / 4, or 1/sqrt(6).
+    const PINK_RMS: f32 = 0.1903;
+    const BROWN_RMS: f32 = 0.2014;
+    const BLUE_RMS: f32 = 0.4082;
+
+    pub fn new(color: f32, rng: StdRng) -> Self {
+        Self {
+            color: color.clamp(-1f32, 1f32),
+            rng,
+            pink: [0f32; 3],
+            brown: 0f32,
+            previous: 0f32,
+        }
+    }
+
+    pub fn process(&mut self, white: f32) -> f32 {
+        if self.color == 0f32 {
+            return white;
+        }
+
+        let uniform = self.rng.gen::<f32>() * 2f32 - 1f32;
+
+        if self.color > 0f32 {
+            let blue =
+                0.5 * (uniform - self.previous) * NoiseColor::WHITE_RMS / NoiseColor::BLUE_RMS;
+            self.previous = uniform;
+
+            return crossfade(white, blue, self.color);
+        }
+
+        // Paul Kellet's economy pink filter and a leaky integrator for brown.
+        self.pink[0] = 0.99765 * self.pink[0] + uniform * 0.0990460;
+        self.pink[1] = 0.96300 * self.pink[1] + uniform * 0.2965164;
+        self.pink[2] = 0.57000 * self.pink[2] + uniform * 1.0526913;
+        let pink = 0.11 * (self.pink.iter().sum::<f32>() + uniform * 0.1848);
+        let pink = pink * NoiseColor::WHITE_RMS / NoiseColor::PINK_RMS;
+        self.brown = (self.brown + 0.02 * uniform) / 1.02;
+        let brown = 3.5 * self.brown * NoiseColor::WHITE_RMS / NoiseColor::BROWN_RMS;
+
+        if self.color >= -0.5 {
+            crossfade(white, pink, -self.color * 2f32)
+        } else {
+            // Both come from the same uniform source, so they add coherently
+            // enough that a linear mix dips by less than 0.5 dB.
+            mix(pink, brown, -self.color * 2f32 - 1f32)
+        }
+    }
+}
+
+fn mix(from: f32, to: f32, amount: f32) -> f32 {
+    from + (to - from) * amount
+}
+
+/// Equal-power crossfade, which keeps the RMS of two independent signals of
+/// the same RMS.
+fn crossfade(from: f32, to: f32, amount: f32) -> f32 {
+    let angle = amount * FRAC_PI_2;
+
+    from * angle.cos() + to * angle.sin()
+}
+
+/// Parses a noise color given by name (white, pink, brown or blue) or as a
+/// number from -1 to 1.
+pub fn parse_color(s: &str) -> Result<f32, anyhow::Error> {
+    match s {
+        "white" => Ok(0f32),
+        "pink" => Ok(-0.5),
+        "brown" => Ok(-1f32),
+        "blue" => Ok(1f32),
+        _ => match s.parse::<f32>() {
+            Ok(color) if (-1f32..=1f32).contains(&color) => Ok(color),
+            Ok(_) => Err(anyhow::anyhow!("Noise color must be from -1 to 1")),
+            Err(_) => Err(anyhow::anyhow!("Unknown noise color")),
+        },
+    }
+}
@@ -0,0 +1,48 @@
+const NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// MIDI note number of A4 at 440 Hz.
+const A4: f32 = 69f32;
+
+/// Nearest equal-tempered note name to `frequency`, e.g. "A4", and how far the
+/// frequency lies from it in cents. Notes are clamped to the MIDI range C-1 to
+/// G9, so frequencies outside it report a large cents offset instead of an
+/// implausible octave.
+pub fn hz_to_note(frequency: f32) -> (String, i32) {
+    let note = A4 + 12f32 * (frequency.max(f32::MIN_POSITIVE) / 440f32).log2();
+    let nearest = note.round().clamp(0f32, 127f32);
+    let cents = ((note - nearest) * 100f32).round() as i32;
+    let nearest = nearest as usize;
+    let name = format!("{}{}", NAMES[nearest % 12], nearest as i32 / 12 - 1);
+
+    (name, cents)
+}
+
+/// Equal-tempered frequency of a note name such as "A4", "C#5" or "Bb3", the
+/// inverse of `hz_to_note`. Returns None for anything else.
+pub fn note_to_hz(name: &str) -> Option<f32> {
+    let mut chars = name.chars();
+    let letter = chars.next()?.to_ascii_uppercase();
+    let mut semitone = NAMES.iter().position(|&note| note == letter.to_string())? as i32;
+    let rest = chars.as_str();
+    let octave = if let Some(octave) = rest.strip_prefix('#') {
+        semitone += 1;
+        octave
+    } else if let Some(octave) = rest.strip_prefix('b') {
+        semitone -= 1;
+        octave
+    } else {
+        rest
+    };
+    let note = (octave.parse::<i32>().ok()? + 1) * 12 + semitone;
+
+    Some(440f32 * 2f32.powf((note as f32 - A4) / 12f32))
+}
+
+/// Formats `frequency` with its nearest note, e.g. "440.0 Hz (A4 +0c)".
+pub fn describe(frequency: f32) -> String {
+    let (name, cents) = hz_to_note(frequency);
+
+    format!("{:.1} Hz ({} {:+}c)", frequency, name, cents)
+}
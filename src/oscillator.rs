@@ -0,0 +1,834 @@
+use {
+    crate::{
+        detect_clicks, detect_frame_clicks,
+        effects::{delay::Delay, reverb::Reverb},
+        envelope::Envelope,
+        filter::LowPass,
+        lfo::{Lfo, ModTarget},
+        noise::NoiseColor,
+        render, render_samples,
+        score::{Melody, ScoreEvent},
+        soft_clip,
+        source_file::SourceFile,
+        staircase,
+        sweep::{Sweep, SweepMode, SweepRange},
+        voice_bank::VoiceBank,
+        wavetable::Wavetable,
+        Alignment, AntiAliasing, Dtmf, Waveform, WaveformRequest,
+    },
+    rand::{rngs::StdRng, SeedableRng},
+    std::path::PathBuf,
+    std::time::Duration,
+};
+
+/// Everything that shapes the sound, from the source through its voices, the
+/// envelope and the effects, independent of any audio device. The defaults
+/// match those of the command line.
+#[derive(Debug, Clone)]
+pub struct Oscillator {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    /// How long the envelope is held on, unless a score or sweep sets it.
+    pub time: Duration,
+    pub volume: f32,
+    /// Frequencies to play together in place of `frequency`.
+    pub chord: Vec<f32>,
+    /// Play a two-operator FM voice instead of `waveform`.
+    pub fm: bool,
+    pub fm_ratio: f32,
+    pub fm_index: f32,
+    pub unison: u8,
+    /// Spread in cents between the lowest and highest unison copies.
+    pub detune: f32,
+    pub sub_level: f32,
+    pub sub_octave: u8,
+    pub ring_mod: bool,
+    pub ring_freq: f32,
+    pub cutoff: Option<f32>,
+    pub resonance: f32,
+    pub delay_ms: f32,
+    pub delay_feedback: f32,
+    pub delay_mix: f32,
+    pub reverb: bool,
+    pub reverb_room: f32,
+    pub reverb_wet: f32,
+    pub soft_clip: bool,
+    pub lfo_target: ModTarget,
+    pub lfo_rate: f32,
+    pub lfo_depth: f32,
+    pub lfo_waveform: Waveform,
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+    /// Length in milliseconds of the fade in at the start and the shortest
+    /// release, so the output never jumps from or to silence.
+    pub fade_ms: f32,
+    /// The cycle the wavetable waveform plays.
+    pub table: Option<Wavetable>,
+    pub duty_cycle: f32,
+    pub noise_color: f32,
+    /// Seed for the noise waveform and noise LFO; None draws from entropy.
+    pub seed: Option<u64>,
+    pub anti_aliasing: AntiAliasing,
+    #[cfg(feature = "band-limited")]
+    pub max_harmonics: u32,
+    #[cfg(feature = "band-limited")]
+    pub adaptive_harmonics: bool,
+    pub dtmf: Option<Dtmf>,
+    pub alignment: Option<Alignment>,
+    pub staircase: Option<u32>,
+    pub step_duration: f32,
+    /// A WAV file to loop instead of synthesizing.
+    pub source_file: Option<PathBuf>,
+    /// Notes to play in turn, retriggering the envelope for each.
+    pub score: Vec<ScoreEvent>,
+    pub sweep: Option<SweepRange>,
+    pub sweep_mode: SweepMode,
+    pub sweep_hold: bool,
+    /// Log sample-to-sample jumps above this threshold to stderr.
+    pub detect_clicks: Option<f32>,
+    /// Play the right channel, every chord voice included, this many Hz above
+    /// the left.
+    pub binaural_beat: Option<f32>,
+}
+
+impl Default for Oscillator {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::SINE,
+            frequency: 440f32,
+            time: Duration::from_secs(1),
+            volume: 1f32,
+            chord: Vec::new(),
+            fm: false,
+            fm_ratio: 1f32,
+            fm_index: 1f32,
+            unison: 1,
+            detune: 20f32,
+            sub_level: 0f32,
+            sub_octave: 1,
+            ring_mod: false,
+            ring_freq: 100f32,
+            cutoff: None,
+            resonance: 0f32,
+            delay_ms: 0f32,
+            delay_feedback: 0.3,
+            delay_mix: 0.5,
+            reverb: false,
+            reverb_room: 0.5,
+            reverb_wet: 0.3,
+            soft_clip: false,
+            lfo_target: ModTarget::None,
+            lfo_rate: 5f32,
+            lfo_depth: 0.1,
+            lfo_waveform: Waveform::SINE,
+            attack: 0f32,
+            decay: 0f32,
+            sustain: 1f32,
+            release: 0f32,
+            fade_ms: 5f32,
+            table: None,
+            duty_cycle: 0.5,
+            noise_color: 0f32,
+            seed: None,
+            anti_aliasing: AntiAliasing::default(),
+            #[cfg(feature = "band-limited")]
+            max_harmonics: 49,
+            #[cfg(feature = "band-limited")]
+            adaptive_harmonics: false,
+            dtmf: None,
+            alignment: None,
+            staircase: None,
+            step_duration: 0.5,
+            source_file: None,
+            score: Vec::new(),
+            sweep: None,
+            sweep_mode: SweepMode::Logarithmic,
+            sweep_hold: false,
+            detect_clicks: None,
+            binaural_beat: None,
+        }
+    }
+}
+
+impl Oscillator {
+    /// Renders `duration` of the mono output at `sample_rate` into memory.
+    pub fn render(&self, duration: Duration, sample_rate: u32) -> Result<Vec<f32>, anyhow::Error> {
+        let mut next_sample = self.samples(sample_rate as f32)?;
+
+        Ok(render(&mut next_sample, sample_rate, duration))
+    }
+
+    /// Renders exactly `n` samples of the mono output at `sample_rate`, e.g.
+    /// for analysis or tests.
+    pub fn render_samples(&self, n: usize, sample_rate: u32) -> Result<Vec<f32>, anyhow::Error> {
+        let mut next_sample = self.samples(sample_rate as f32)?;
+
+        Ok(render_samples(&mut next_sample, n))
+    }
+
+    /// The gain in dB that loudness normalization gives a full-scale sine:
+    /// `Waveform::normalized_level_dbfs` for synthesized voices, and 0 for
+    /// DTMF and alignment tones, staircases and source files, which play as
+    /// they are.
+    pub fn normalization_db(&self) -> f32 {
+        match (self.dtmf, self.alignment, self.staircase, &self.source_file) {
+            (None, None, None, None) => Waveform::normalized_level_dbfs(),
+            _ => 0f32,
+        }
+    }
+
+    /// How long a note plays: the gate plus the envelope's release.
+    pub fn duration(&self) -> Duration {
+        self.gate() + Duration::from_secs_f32(self.release_seconds())
+    }
+
+    /// The envelope's release, lengthened to the fade so that the end of the
+    /// gate ramps down to silence instead of cutting off.
+    fn release_seconds(&self) -> f32 {
+        self.release.max(self.fade_ms / 1000f32).max(0f32)
+    }
+
+    /// How long the envelope is held on: the length of the score, or of the
+    /// sweep unless `sweep_hold` is set, or otherwise `time`.
+    pub fn gate(&self) -> Duration {
+        if !self.score.is_empty() {
+            return Duration::from_secs_f32(self.score.iter().map(|event| event.duration).sum());
+        }
+
+        match self.sweep {
+            Some(range) if !self.sweep_hold => Duration::from_secs_f32(range.seconds),
+            _ => self.time,
+        }
+    }
+
+    /// The sample ranges during which the envelope is gated on: every note of
+    /// the score, or otherwise the whole gate.
+    fn gates(&self, sample_rate: f32) -> Vec<(u64, u64)> {
+        if self.score.is_empty() {
+            return vec![(0, (self.gate().as_secs_f32() * sample_rate) as u64)];
+        }
+
+        let mut start = 0f32;
+        self.score
+            .iter()
+            .filter_map(|event| {
+                let gate = (
+                    (start * sample_rate) as u64,
+                    ((start + event.duration) * sample_rate) as u64,
+                );
+                start += event.duration;
+                event.frequency.map(|_| gate)
+            })
+            .collect()
+    }
+
+    /// The mono output: the source, filtered, shaped by the gain envelope and
+    /// passed through the delay, the reverb and the output clip.
+    pub fn samples(
+        &self,
+        sample_rate: f32,
+    ) -> Result<Box<dyn FnMut() -> f32 + Send>, anyhow::Error> {
+        let mut source = self.source(sample_rate)?;
+        let mut filter = self.low_pass(sample_rate);
+        let mut gain = self.gain(sample_rate);
+        let mut delay = self.delay(sample_rate);
+        let mut reverb = self.reverb(sample_rate);
+        let clip = self.output_clip();
+        let source: Box<dyn FnMut() -> f32 + Send> = Box::new(move || {
+            clip(reverb.process(delay.process(gain() * filter.process(source()))))
+        });
+
+        Ok(match self.detect_clicks {
+            Some(threshold) => detect_clicks(source, threshold, sample_rate, self.describe()),
+            None => source,
+        })
+    }
+
+    /// Builds a generator of (left, right) frames. Both sides carry the same
+    /// signal unless a binaural beat detunes the right channel or a stereo
+    /// source file is playing.
+    pub fn frames(
+        &self,
+        sample_rate: f32,
+    ) -> Result<Box<dyn FnMut() -> (f32, f32) + Send>, anyhow::Error> {
+        if let Some(path) = &self.source_file {
+            let mut frames = SourceFile::load(path, sample_rate as u32)?.frames();
+            let (mut left_filter, mut right_filter) =
+                (self.low_pass(sample_rate), self.low_pass(sample_rate));
+            let (mut left_delay, mut right_delay) =
+                (self.delay(sample_rate), self.delay(sample_rate));
+            let (mut left_reverb, mut right_reverb) =
+                (self.reverb(sample_rate), self.reverb(sample_rate));
+            let mut gain = self.gain(sample_rate);
+            let clip = self.output_clip();
+            let frames: Box<dyn FnMut() -> (f32, f32) + Send> = Box::new(move || {
+                let (left, right) = frames();
+                let gain = gain();
+                let left = left_delay.process(gain * left_filter.process(left));
+                let right = right_delay.process(gain * right_filter.process(right));
+                (
+                    clip(left_reverb.process(left)),
+                    clip(right_reverb.process(right)),
+                )
+            });
+
+            return Ok(match self.detect_clicks {
+                Some(threshold) => {
+                    detect_frame_clicks(frames, threshold, sample_rate, self.describe())
+                }
+                None => frames,
+            });
+        }
+
+        let mut left = self.samples(sample_rate)?;
+
+        Ok(match self.binaural_beat {
+            Some(beat) => {
+                let mut right = self.clone();
+                right.frequency += beat;
+                for frequency in &mut right.chord {
+                    *frequency += beat;
+                }
+                let mut right = right.samples(sample_rate)?;
+
+                Box::new(move || (left(), right()))
+            }
+            None => Box::new(move || {
+                let value = left();
+                (value, value)
+            }),
+        })
+    }
+
+    /// What is playing, for the messages of `detect_clicks`.
+    fn describe(&self) -> String {
+        match (self.dtmf, self.alignment, self.staircase) {
+            (Some(dtmf), _, _) => format!("dtmf {} + {} Hz", dtmf.low, dtmf.high),
+            (_, Some(_), _) => format!("alignment {} Hz", Alignment::FREQUENCY),
+            (_, _, Some(steps)) => format!("staircase of {} steps", steps),
+            _ => match (&self.source_file, self.sweep) {
+                (Some(path), _) => format!("file {}", path.display()),
+                (None, Some(range)) => format!(
+                    "{} {} sweep {}-{} Hz over {}s",
+                    self.waveform, self.sweep_mode, range.start, range.end, range.seconds
+                ),
+                (None, None) if !self.score.is_empty() => {
+                    format!("score of {} events", self.score.len())
+                }
+                (None, None) if self.fm => format!(
+                    "fm {} Hz, ratio {}, index {}",
+                    self.frequency, self.fm_ratio, self.fm_index
+                ),
+                (None, None) => format!("{} {} Hz", self.waveform, self.frequency),
+            },
+        }
+    }
+
+    /// The final output stage: `soft_clip` when enabled, otherwise unchanged.
+    fn output_clip(&self) -> fn(f32) -> f32 {
+        if self.soft_clip {
+            soft_clip
+        } else {
+            |sample| sample
+        }
+    }
+
+    fn low_pass(&self, sample_rate: f32) -> LowPass {
+        LowPass::new(
+            self.cutoff.unwrap_or(f32::INFINITY),
+            self.resonance,
+            sample_rate,
+        )
+    }
+
+    /// The reverb, bypassed unless `reverb` is set.
+    fn reverb(&self, sample_rate: f32) -> Reverb {
+        let wet = if self.reverb { self.reverb_wet } else { 0f32 };
+
+        Reverb::new(self.reverb_room, wet, sample_rate)
+    }
+
+    fn delay(&self, sample_rate: f32) -> Delay {
+        Delay::new(
+            self.delay_ms,
+            self.delay_feedback,
+            self.delay_mix,
+            sample_rate,
+        )
+    }
+
+    /// Per-sample output gain: the volume faded in over `fade_ms` and shaped
+    /// by an envelope that is triggered at the start and released once the
+    /// gate has elapsed, or for each note of the score.
+    fn gain(&self, sample_rate: f32) -> Box<dyn FnMut() -> f32 + Send> {
+        let volume = self.volume.clamp(0f32, 1f32);
+        let fade_step = match self.fade_ms.max(0f32) * sample_rate / 1000f32 {
+            samples if samples >= 1f32 => 1f32 / samples,
+            _ => 1f32,
+        };
+        let mut fade = 0f32;
+        let mut gates = self.gates(sample_rate).into_iter().peekable();
+        let mut envelope = Envelope::new(
+            self.attack,
+            self.decay,
+            self.sustain,
+            self.release_seconds(),
+            sample_rate,
+        );
+        let mut tremolo = match self.lfo_target {
+            ModTarget::Volume => Some(Lfo::new(
+                self.lfo_waveform,
+                self.lfo_rate,
+                self.lfo_depth,
+                sample_rate,
+                self.noise_rng(3),
+            )),
+            ModTarget::Frequency | ModTarget::None => None,
+        };
+        let mut elapsed = 0u64;
+
+        Box::new(move || {
+            // A gate that ends where the next starts retriggers the envelope.
+            while let Some(&(start, end)) = gates.peek() {
+                if elapsed == end {
+                    envelope.note_off();
+                    gates.next();
+                } else {
+                    if elapsed == start {
+                        envelope.note_on();
+                    }
+                    break;
+                }
+            }
+            elapsed += 1;
+            fade = (fade + fade_step).min(1f32);
+
+            let gain = volume * fade * envelope.next_level();
+            match &mut tremolo {
+                Some(lfo) => gain * lfo.gain(),
+                None => gain,
+            }
+        })
+    }
+
+    fn source(&self, sample_rate: f32) -> Result<Box<dyn FnMut() -> f32 + Send>, anyhow::Error> {
+        if let Some(dtmf) = self.dtmf {
+            return Ok(dtmf.tone(sample_rate));
+        }
+        if let Some(alignment) = self.alignment {
+            return Ok(alignment.tone(sample_rate));
+        }
+        if let Some(steps) = self.staircase {
+            return Ok(staircase(steps, self.step_duration, sample_rate));
+        }
+        if let Some(path) = &self.source_file {
+            return Ok(SourceFile::load(path, sample_rate as u32)?.mono());
+        }
+
+        if !self.score.is_empty() {
+            return self.score_voices(sample_rate);
+        }
+
+        if let Some(range) = self.sweep {
+            return self.voice(range.start, sample_rate);
+        }
+
+        if self.chord.is_empty() {
+            return self.voice(self.frequency, sample_rate);
+        }
+
+        let mut bank = VoiceBank::new(
+            self.chord
+                .iter()
+                .map(|&frequency| self.voice(frequency, sample_rate))
+                .collect::<Result<_, _>>()?,
+        );
+
+        Ok(Box::new(move || bank.next_sample()))
+    }
+
+    /// Plays each note of the score in turn on one voice, retuned through the
+    /// frequency modulation of `request` so the phase runs on across notes.
+    fn score_voices(
+        &self,
+        sample_rate: f32,
+    ) -> Result<Box<dyn FnMut() -> f32 + Send>, anyhow::Error> {
+        self.voice(self.score_reference(), sample_rate)
+    }
+
+    /// The frequency the score's voice is built at, which `Melody` retunes
+    /// relative to: that of the first note.
+    fn score_reference(&self) -> f32 {
+        self.score
+            .iter()
+            .find_map(|event| event.frequency)
+            .unwrap_or(self.frequency)
+    }
+
+    /// One synthesized voice of the selected waveform at `frequency`, with the
+    /// sub-oscillator mixed in when `sub_level` is above zero and the result
+    /// ring-modulated when `ring_mod` is set.
+    fn voice(
+        &self,
+        frequency: f32,
+        sample_rate: f32,
+    ) -> Result<Box<dyn FnMut() -> f32 + Send>, anyhow::Error> {
+        let mut voice = self.sub_voice(frequency, sample_rate)?;
+
+        if !self.ring_mod {
+            return Ok(voice);
+        }
+
+        let mut ring = WaveformRequest::new(self.ring_freq, 0f32, sample_rate).sine();
+
+        Ok(Box::new(move || voice() * ring()))
+    }
+
+    /// The unison voices with the sub-oscillator mixed under them.
+    fn sub_voice(
+        &self,
+        frequency: f32,
+        sample_rate: f32,
+    ) -> Result<Box<dyn FnMut() -> f32 + Send>, anyhow::Error> {
+        let mut voice = self.unison_voices(frequency, sample_rate)?;
+
+        if self.sub_level <= 0f32 {
+            return Ok(voice);
+        }
+
+        let divisor = 2f32.powi(self.sub_octave as i32);
+        let mut sub = self.request(frequency / divisor, sample_rate).sine();
+        let level = self.sub_level;
+
+        Ok(Box::new(move || voice() + level * sub()))
+    }
+
+    /// `unison` copies of the oscillator spread evenly across `detune` cents
+    /// around `frequency` and mixed at the level of one. A single voice plays
+    /// `frequency` exactly.
+    fn unison_voices(
+        &self,
+        frequency: f32,
+        sample_rate: f32,
+    ) -> Result<Box<dyn FnMut() -> f32 + Send>, anyhow::Error> {
+        if self.unison <= 1 {
+            return self.single_voice(frequency, sample_rate);
+        }
+
+        let last = (self.unison - 1) as f32;
+        let mut bank = VoiceBank::new(
+            (0..self.unison)
+                .map(|voice| {
+                    let cents = self.detune * (voice as f32 / last - 0.5);
+                    let detuned = frequency * 2f32.powf(cents / 1200f32);
+                    self.single_voice(detuned, sample_rate)
+                })
+                .collect::<Result<_, _>>()?,
+        );
+
+        Ok(Box::new(move || bank.next_sample()))
+    }
+
+    /// The selected waveform or the FM voice at `frequency`, normalized to a
+    /// common loudness. The wavetable waveform fails without a `table`.
+    fn single_voice(
+        &self,
+        frequency: f32,
+        sample_rate: f32,
+    ) -> Result<Box<dyn FnMut() -> f32 + Send>, anyhow::Error> {
+        let waveform_req = self.request(frequency, sample_rate);
+        let gain = if self.fm {
+            Waveform::fm_normalization(self.fm_ratio, self.fm_index)
+        } else {
+            self.waveform
+                .normalization(self.anti_aliasing, self.duty_cycle)
+        };
+        let mut oscillator = match self.waveform {
+            _ if self.fm => waveform_req.fm(self.fm_ratio, self.fm_index),
+            Waveform::SINE => waveform_req.sine(),
+            Waveform::SAWTOOTH => waveform_req.sawtooth(),
+            Waveform::TRIANGLE => waveform_req.triangle(),
+            Waveform::SQUARE => waveform_req.square(),
+            Waveform::PULSE => waveform_req.pulse(self.duty_cycle),
+            Waveform::WAVETABLE => match &self.table {
+                Some(table) => waveform_req.wavetable(table.clone()),
+                None => return Err(anyhow::anyhow!("the wavetable waveform needs a table")),
+            },
+            Waveform::NOISE => {
+                let mut white = waveform_req.white_noise(self.noise_rng(0));
+                let mut color = NoiseColor::new(self.noise_color, self.noise_rng(1));
+
+                Box::new(move || color.process(white()))
+            }
+        };
+
+        Ok(Box::new(move || gain * oscillator()))
+    }
+
+    /// A `WaveformRequest` at `frequency` with the anti-aliasing, harmonics,
+    /// vibrato, sweep and score notes chosen here.
+    fn request(&self, frequency: f32, sample_rate: f32) -> WaveformRequest {
+        let waveform_req = WaveformRequest::new(frequency, 0f32, sample_rate)
+            .with_anti_aliasing(self.anti_aliasing);
+        let mut vibrato = match self.lfo_target {
+            ModTarget::Frequency => Some(Lfo::new(
+                self.lfo_waveform,
+                self.lfo_rate,
+                self.lfo_depth,
+                sample_rate,
+                self.noise_rng(2),
+            )),
+            ModTarget::Volume | ModTarget::None => None,
+        };
+        let mut sweep = self
+            .sweep
+            .map(|range| Sweep::new(range, self.sweep_mode, sample_rate));
+        let mut melody = (!self.score.is_empty())
+            .then(|| Melody::new(&self.score, self.score_reference(), sample_rate));
+        let waveform_req = if vibrato.is_none() && sweep.is_none() && melody.is_none() {
+            waveform_req
+        } else {
+            waveform_req.with_frequency_modulation(Box::new(move || {
+                let vibrato = vibrato.as_mut().map_or(1f32, |lfo| lfo.frequency_ratio());
+                let sweep = sweep.as_mut().map_or(1f32, |sweep| sweep.ratio());
+                let melody = melody.as_mut().map_or(1f32, |melody| melody.ratio());
+                vibrato * sweep * melody
+            }))
+        };
+        #[cfg(feature = "band-limited")]
+        let waveform_req = waveform_req
+            .with_max_harmonics(self.max_harmonics)
+            .with_adaptive_harmonics(self.adaptive_harmonics);
+
+        waveform_req
+    }
+
+    /// Random source for one noise generator: seeded from `seed` when given so
+    /// renders repeat exactly, otherwise from entropy. Each generator passes
+    /// its own `stream` so they don't share a sequence. The pair is mixed
+    /// rather than added, so neighbouring seeds don't reuse each other's
+    /// streams.
+    fn noise_rng(&self, stream: u64) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(splitmix64(splitmix64(seed) ^ stream)),
+            None => StdRng::from_entropy(),
+        }
+    }
+}
+
+/// The SplitMix64 finalizer: a bijection on u64 that scatters nearby inputs.
+fn splitmix64(x: u64) -> u64 {
+    let x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, rand::RngCore, std::f32::consts::FRAC_1_SQRT_2};
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn noise(seed: u64) -> Vec<f32> {
+        Oscillator {
+            waveform: Waveform::NOISE,
+            seed: Some(seed),
+            ..Default::default()
+        }
+        .render(Duration::from_millis(100), SAMPLE_RATE)
+        .unwrap()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    /// One second of every waveform, FM and noise color plays at the RMS of
+    /// the normalized level, and a sine peaks at that level.
+    #[test]
+    fn every_source_plays_at_the_normalized_level() {
+        let level = 10f32.powf(Waveform::normalized_level_dbfs() / 20f32);
+        let measure = |oscillator: Oscillator| {
+            rms(&oscillator.render(oscillator.time, SAMPLE_RATE).unwrap()) / FRAC_1_SQRT_2
+        };
+        let mut sources = Vec::new();
+        for (fm_ratio, fm_index) in [(1f32, 1f32), (2f32, 3f32), (1.5, 2f32)] {
+            let oscillator = Oscillator {
+                fm: true,
+                fm_ratio,
+                fm_index,
+                ..Default::default()
+            };
+            sources.push((format!("fm {} {}", fm_ratio, fm_index), oscillator, 0.03));
+        }
+        let waveforms = [
+            Waveform::SINE,
+            Waveform::SAWTOOTH,
+            Waveform::TRIANGLE,
+            Waveform::SQUARE,
+            Waveform::PULSE,
+        ];
+        let anti_aliasing_modes = [
+            #[cfg(feature = "band-limited")]
+            AntiAliasing::Additive,
+            AntiAliasing::PolyBlep,
+            AntiAliasing::None,
+        ];
+        for anti_aliasing in anti_aliasing_modes {
+            for waveform in waveforms {
+                let oscillator = Oscillator {
+                    waveform,
+                    anti_aliasing,
+                    ..Default::default()
+                };
+                sources.push((format!("{} {}", waveform, anti_aliasing), oscillator, 0.03));
+            }
+        }
+        // Noise is random and the pink to brown mix dips slightly.
+        for noise_color in [-1f32, -0.75, -0.5, -0.25, 0f32, 0.5, 1f32] {
+            let oscillator = Oscillator {
+                waveform: Waveform::NOISE,
+                noise_color,
+                seed: Some(1),
+                ..Default::default()
+            };
+            sources.push((format!("noise {}", noise_color), oscillator, 0.1));
+        }
+
+        for (name, oscillator, tolerance) in sources {
+            let measured = measure(oscillator);
+            assert!(
+                (measured / level - 1f32).abs() < tolerance,
+                "{}: {} against {}",
+                name,
+                measured,
+                level
+            );
+        }
+
+        let sine = Oscillator::default()
+            .render(Duration::from_secs(1), SAMPLE_RATE)
+            .unwrap();
+        let peak = sine
+            .iter()
+            .fold(0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((peak / level - 1f32).abs() < 1e-3, "sine peaks at {}", peak);
+    }
+
+    #[test]
+    fn renders_one_second_of_finite_samples() {
+        let samples = Oscillator::default()
+            .render_samples(48000, SAMPLE_RATE)
+            .unwrap();
+
+        assert_eq!(samples.len(), 48000);
+        assert!(samples.iter().all(|sample| sample.is_finite()));
+    }
+
+    #[test]
+    fn renders_exactly_the_samples_asked_for() {
+        for n in [0, 1, 480, 4801] {
+            let samples = Oscillator::default()
+                .render_samples(n, SAMPLE_RATE)
+                .unwrap();
+
+            assert_eq!(samples.len(), n);
+        }
+    }
+
+    #[test]
+    fn wavetable_without_a_table_is_an_error() {
+        let oscillator = Oscillator {
+            waveform: Waveform::WAVETABLE,
+            ..Default::default()
+        };
+
+        assert!(oscillator
+            .render(Duration::from_secs(1), SAMPLE_RATE)
+            .is_err());
+        assert!(oscillator.frames(SAMPLE_RATE as f32).is_err());
+    }
+
+    #[test]
+    fn fades_in_and_out() {
+        let oscillator = Oscillator {
+            waveform: Waveform::SQUARE,
+            ..Default::default()
+        };
+        let samples = oscillator
+            .render(oscillator.duration(), SAMPLE_RATE)
+            .unwrap();
+        let (first, last) = (&samples[..8], &samples[samples.len() - 8..]);
+
+        assert!(
+            first.iter().all(|sample| sample.abs() < 0.05),
+            "{:?}",
+            first
+        );
+        assert!(last.iter().all(|sample| sample.abs() < 0.05), "{:?}", last);
+    }
+
+    /// With no attack to hide it, restarting the voice at each note would jump
+    /// by up to full scale; a continuous phase steps no further than the
+    /// higher note does within one sample.
+    #[test]
+    fn score_changes_notes_without_a_click() {
+        let note = |frequency| ScoreEvent {
+            frequency: Some(frequency),
+            duration: 0.1,
+        };
+        let oscillator = Oscillator {
+            score: vec![note(440f32), note(523.25), note(659.25)],
+            ..Default::default()
+        };
+        let samples = oscillator.render(oscillator.gate(), SAMPLE_RATE).unwrap();
+
+        for change in [4800, 9600] {
+            let step = samples[change - 8..change + 8]
+                .windows(2)
+                .fold(0f32, |step, pair| step.max((pair[1] - pair[0]).abs()));
+            // 2 pi * 659.25 Hz / 48 kHz
+            assert!(step < 0.09, "step of {} at sample {}", step, change);
+        }
+    }
+
+    #[test]
+    fn binaural_beat_detunes_chords() {
+        let oscillator = Oscillator {
+            chord: vec![200f32, 300f32],
+            binaural_beat: Some(8f32),
+            ..Default::default()
+        };
+        let mut frames = oscillator.frames(SAMPLE_RATE as f32).unwrap();
+        let frames: Vec<(f32, f32)> = (0..4800).map(|_| frames()).collect();
+
+        assert!(frames
+            .iter()
+            .any(|(left, right)| (left - right).abs() > 0.1));
+    }
+
+    #[test]
+    fn seeded_noise_repeats() {
+        assert_eq!(noise(7), noise(7));
+        assert_ne!(noise(7), noise(8));
+    }
+
+    #[test]
+    fn neighbouring_seeds_do_not_share_noise_streams() {
+        let first_values = |seed: u64, stream: u64| {
+            let oscillator = Oscillator {
+                seed: Some(seed),
+                ..Default::default()
+            };
+            let mut rng = oscillator.noise_rng(stream);
+            (0..8).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+
+        for seed in [0, 7, u64::MAX - 1] {
+            assert_ne!(first_values(seed + 1, 0), first_values(seed, 1), "{}", seed);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use {
+    crate::Args,
+    gen_waveform::{lfo::ModTarget, Waveform},
+    serde::{Deserialize, Serialize},
+    std::path::Path,
+};
+
+/// The tone-shaping parameters worth keeping between runs. Output routing,
+/// devices and alternative sources stay on the command line.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Preset {
+    waveform: String,
+    frequency: u32,
+    volume: f32,
+    duty_cycle: f32,
+    noise_color: f32,
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    pan: Option<f32>,
+    cutoff: Option<f32>,
+    resonance: f32,
+    lfo_target: String,
+    lfo_rate: f32,
+    lfo_depth: f32,
+    lfo_waveform: String,
+}
+
+impl Preset {
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            waveform: args.waveform.to_string(),
+            frequency: args.frequency,
+            volume: args.volume,
+            duty_cycle: args.duty_cycle,
+            noise_color: args.noise_color,
+            attack: args.attack,
+            decay: args.decay,
+            sustain: args.sustain,
+            release: args.release,
+            pan: args.pan,
+            cutoff: args.cutoff,
+            resonance: args.resonance,
+            lfo_target: args.lfo_target.to_string(),
+            lfo_rate: args.lfo_rate,
+            lfo_depth: args.lfo_depth,
+            lfo_waveform: args.lfo_waveform.to_string(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("failed to read {}: {}", path.display(), err))?;
+
+        toml::from_str(&text)
+            .map_err(|err| anyhow::anyhow!("invalid preset {}: {}", path.display(), err))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), anyhow::Error> {
+        let path = path.as_ref();
+        std::fs::write(path, toml::to_string(self)?)
+            .map_err(|err| anyhow::anyhow!("failed to write {}: {}", path.display(), err))
+    }
+
+    /// Overwrites the matching fields of `args`, clamping levels and times to
+    /// the ranges the command line documents.
+    pub fn apply(self, args: &mut Args) -> Result<(), anyhow::Error> {
+        if self.frequency == 0 {
+            return Err(anyhow::anyhow!("preset frequency must be above 0 Hz"));
+        }
+
+        args.waveform = self.waveform.parse::<Waveform>()?;
+        args.frequency = self.frequency;
+        args.volume = self.volume.clamp(0f32, 1f32);
+        args.duty_cycle = self.duty_cycle.clamp(0.01, 0.99);
+        args.noise_color = self.noise_color.clamp(-1f32, 1f32);
+        args.attack = self.attack.max(0f32);
+        args.decay = self.decay.max(0f32);
+        args.sustain = self.sustain.clamp(0f32, 1f32);
+        args.release = self.release.max(0f32);
+        args.pan = self.pan.map(|pan| pan.clamp(-1f32, 1f32));
+        args.cutoff = self.cutoff.map(|cutoff| cutoff.max(1f32));
+        args.resonance = self.resonance.clamp(0f32, 1f32);
+        args.lfo_target = self.lfo_target.parse::<ModTarget>()?;
+        args.lfo_rate = self.lfo_rate.max(0f32);
+        args.lfo_depth = self.lfo_depth.clamp(0f32, 1f32);
+        args.lfo_waveform = self.lfo_waveform.parse::<Waveform>()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use {
+    std::fmt,
+    std::fmt::Display,
+    std::io,
+    std::io::Write,
+    std::str::FromStr,
+    std::time::{Duration, Instant},
+};
+
+/// Blocks written per second of audio when streaming raw PCM.
+const BLOCKS_PER_SECOND: u32 = 100;
+
+/// Sample encoding for raw PCM output. Samples are mono and little-endian.
+#[derive(Debug, Copy, Clone)]
+pub enum RawFormat {
+    F32,
+    I16,
+}
+
+impl FromStr for RawFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
+        match s {
+            "f32" => Ok(RawFormat::F32),
+            "i16" => Ok(RawFormat::I16),
+            _ => Err(anyhow::anyhow!("Unknown raw format")),
+        }
+    }
+}
+
+impl Display for RawFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match &self {
+            RawFormat::F32 => "f32",
+            RawFormat::I16 => "i16",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+impl RawFormat {
+    fn encode(self, value: f32, bytes: &mut Vec<u8>) {
+        match self {
+            RawFormat::F32 => bytes.extend_from_slice(&value.to_le_bytes()),
+            RawFormat::I16 => {
+                let value = (value.clamp(-1f32, 1f32) * i16::MAX as f32) as i16;
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+}
+
+/// Streams `duration` worth of samples to stdout, paced to the wall clock so
+/// that a reader receives them at `sample_rate` rather than as one burst.
+/// A closed pipe ends the stream quietly.
+pub fn stream<F>(
+    next_sample: &mut F,
+    format: RawFormat,
+    sample_rate: u32,
+    duration: Duration,
+) -> Result<(), anyhow::Error>
+where
+    F: FnMut() -> f32 + Send,
+{
+    let total = (duration.as_secs_f64() * sample_rate as f64) as u64;
+    let block = (sample_rate / BLOCKS_PER_SECOND).max(1) as u64;
+    let mut stdout = io::stdout().lock();
+    let mut bytes = Vec::new();
+    let mut written = 0u64;
+    let start = Instant::now();
+
+    while written < total {
+        let count = block.min(total - written);
+
+        bytes.clear();
+        for _ in 0..count {
+            format.encode(next_sample(), &mut bytes);
+        }
+
+        match stdout.write_all(&bytes).and_then(|_| stdout.flush()) {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+        written += count;
+
+        let due = start + Duration::from_secs_f64(written as f64 / sample_rate as f64);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+    }
+
+    Ok(())
+}
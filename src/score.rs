@@ -0,0 +1,103 @@
+use {crate::notes, std::path::Path};
+
+/// One line of a score: a note to play, or a rest when `frequency` is None,
+/// lasting `duration` seconds.
+#[derive(Debug, Copy, Clone)]
+pub struct ScoreEvent {
+    pub frequency: Option<f32>,
+    pub duration: f32,
+}
+
+/// Loads a score of one `note duration` pair per line, e.g. `C#5 0.25` or
+/// `rest 0.5`. Blank lines and lines starting with `#` are skipped.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<ScoreEvent>, anyhow::Error> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| anyhow::anyhow!("failed to read {}: {}", path.display(), err))?;
+
+    parse(&text).map_err(|err| anyhow::anyhow!("invalid score {}: {}", path.display(), err))
+}
+
+pub fn parse(text: &str) -> Result<Vec<ScoreEvent>, anyhow::Error> {
+    let mut events = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fail = |message: String| anyhow::anyhow!("line {}: {}", index + 1, message);
+        let (note, duration) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [note, duration] => (note, duration),
+            _ => return Err(fail(format!("expected `note duration`, got \"{}\"", line))),
+        };
+        let frequency = match note {
+            "rest" => None,
+            _ => Some(
+                notes::note_to_hz(note)
+                    .ok_or_else(|| fail(format!("unknown note \"{}\"", note)))?,
+            ),
+        };
+        let duration = match duration.parse::<f32>() {
+            Ok(duration) if duration.is_finite() && duration > 0f32 => duration,
+            _ => return Err(fail(format!("invalid duration \"{}\"", duration))),
+        };
+
+        events.push(ScoreEvent {
+            frequency,
+            duration,
+        });
+    }
+
+    if events.is_empty() {
+        return Err(anyhow::anyhow!("no notes"));
+    }
+
+    Ok(events)
+}
+
+/// Steps through the notes of a score one sample at a time, so that a single
+/// voice can play them all without restarting its phase. Rests and the time
+/// after the score hold the previous note.
+pub struct Melody {
+    notes: Vec<(u64, f32)>,
+    next: usize,
+    ratio: f32,
+    elapsed: u64,
+}
+
+impl Melody {
+    pub fn new(score: &[ScoreEvent], reference: f32, sample_rate: f32) -> Self {
+        let mut start = 0f32;
+        let mut notes = Vec::new();
+        for event in score {
+            if let Some(frequency) = event.frequency {
+                notes.push(((start * sample_rate) as u64, frequency / reference));
+            }
+            start += event.duration;
+        }
+
+        Self {
+            notes,
+            next: 0,
+            ratio: 1f32,
+            elapsed: 0,
+        }
+    }
+
+    /// The frequency for the next sample as a ratio of `reference`, suitable
+    /// for `WaveformRequest::with_frequency_modulation`.
+    pub fn ratio(&mut self) -> f32 {
+        while let Some(&(start, ratio)) = self.notes.get(self.next) {
+            if start > self.elapsed {
+                break;
+            }
+            self.ratio = ratio;
+            self.next += 1;
+        }
+        self.elapsed += 1;
+
+        self.ratio
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::Path;
+
+/// A WAV file decoded and resampled to the output rate, ready to loop.
+pub struct SourceFile {
+    left: Vec<f32>,
+    right: Vec<f32>,
+}
+
+impl SourceFile {
+    /// Loads `path`, keeping its first two channels (mono files feed both
+    /// sides) and resampling them linearly to `sample_rate`.
+    pub fn load<P: AsRef<Path>>(path: P, sample_rate: u32) -> Result<Self, anyhow::Error> {
+        let (samples, spec) = read_wav(path.as_ref())?;
+        let channels = spec.channels as usize;
+        let left: Vec<f32> = samples.chunks(channels).map(|frame| frame[0]).collect();
+        let right: Vec<f32> = if channels > 1 {
+            samples.chunks(channels).map(|frame| frame[1]).collect()
+        } else {
+            left.clone()
+        };
+
+        Ok(Self {
+            left: resample(&left, spec.sample_rate, sample_rate),
+            right: resample(&right, spec.sample_rate, sample_rate),
+        })
+    }
+
+    /// Loops the file with both channels summed to mono.
+    pub fn mono(self) -> Box<dyn FnMut() -> f32 + Send> {
+        let mut frames = self.frames();
+
+        Box::new(move || {
+            let (left, right) = frames();
+            0.5 * (left + right)
+        })
+    }
+
+    /// Loops the file as (left, right) frames.
+    pub fn frames(self) -> Box<dyn FnMut() -> (f32, f32) + Send> {
+        let mut position = 0;
+
+        Box::new(move || {
+            let frame = (self.left[position], self.right[position]);
+            position = (position + 1) % self.left.len();
+            frame
+        })
+    }
+}
+
+/// Decodes every interleaved sample of a WAV file to the range ±1.0,
+/// failing if it holds less than one frame.
+pub(crate) fn read_wav(path: &Path) -> Result<(Vec<f32>, hound::WavSpec), anyhow::Error> {
+    let reader = hound::WavReader::open(path)
+        .map_err(|err| anyhow::anyhow!("failed to open {}: {}", path.display(), err))?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|sample| sample.map(|value| value as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    if samples.len() < spec.channels as usize {
+        return Err(anyhow::anyhow!("{} contains no samples", path.display()));
+    }
+
+    Ok((samples, spec))
+}
+
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to {
+        return samples.to_vec();
+    }
+
+    let ratio = from as f64 / to as f64;
+    let length = ((samples.len() as f64 / ratio) as usize).max(1);
+
+    (0..length)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let current = samples[index.min(samples.len() - 1)];
+            let next = samples[(index + 1).min(samples.len() - 1)];
+
+            current + (next - current) * fraction
+        })
+        .collect()
+}
@@ -0,0 +1,158 @@
+use {std::fmt, std::fmt::Display, std::str::FromStr};
+
+/// How a sweep moves between its start and end frequencies.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SweepMode {
+    /// Equal steps in Hz per second.
+    Linear,
+    /// Equal steps in octaves per second, spending as long on each octave.
+    Logarithmic,
+}
+
+impl FromStr for SweepMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
+        match s {
+            "lin" => Ok(SweepMode::Linear),
+            "log" => Ok(SweepMode::Logarithmic),
+            _ => Err(anyhow::anyhow!("Unknown sweep mode")),
+        }
+    }
+}
+
+impl Display for SweepMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match &self {
+            SweepMode::Linear => "lin",
+            SweepMode::Logarithmic => "log",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// A sweep from `start` to `end` Hz over `seconds`, parsed from
+/// `start:end:seconds`, e.g. `20:20000:10`.
+#[derive(Debug, Copy, Clone)]
+pub struct SweepRange {
+    pub start: f32,
+    pub end: f32,
+    pub seconds: f32,
+}
+
+impl FromStr for SweepRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
+        let values = s
+            .split(':')
+            .map(|value| value.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| anyhow::anyhow!("Sweep must be start:end:seconds"))?;
+
+        match values[..] {
+            [start, end, seconds] if start > 0f32 && end > 0f32 && seconds > 0f32 => Ok(Self {
+                start,
+                end,
+                seconds,
+            }),
+            [_, _, _] => Err(anyhow::anyhow!(
+                "Sweep frequencies and length must be above 0"
+            )),
+            _ => Err(anyhow::anyhow!("Sweep must be start:end:seconds")),
+        }
+    }
+}
+
+/// Steps through a `SweepRange` one sample at a time, then holds the end
+/// frequency.
+pub struct Sweep {
+    range: SweepRange,
+    mode: SweepMode,
+    samples: f32,
+    elapsed: f32,
+}
+
+impl Sweep {
+    pub fn new(range: SweepRange, mode: SweepMode, sample_rate: f32) -> Self {
+        Self {
+            range,
+            mode,
+            samples: (range.seconds * sample_rate).max(1f32),
+            elapsed: 0f32,
+        }
+    }
+
+    /// The frequency for the next sample as a ratio of the start frequency,
+    /// suitable for `WaveformRequest::with_frequency_modulation`.
+    pub fn ratio(&mut self) -> f32 {
+        let t = (self.elapsed / self.samples).min(1f32);
+        self.elapsed = (self.elapsed + 1f32).min(self.samples);
+        let end = self.range.end / self.range.start;
+
+        match self.mode {
+            SweepMode::Linear => 1f32 + (end - 1f32) * t,
+            SweepMode::Logarithmic => end.powf(t),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One sample per millisecond, so the one-second sweeps below end at
+    /// sample 1000.
+    const SAMPLE_RATE: f32 = 1000f32;
+
+    const RANGE: SweepRange = SweepRange {
+        start: 100f32,
+        end: 400f32,
+        seconds: 1f32,
+    };
+
+    /// The swept frequency in Hz for each of the first `n` samples.
+    fn frequencies(mode: SweepMode, n: usize) -> Vec<f32> {
+        let mut sweep = Sweep::new(RANGE, mode, SAMPLE_RATE);
+        (0..n).map(|_| sweep.ratio() * RANGE.start).collect()
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 0.01,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn linear_sweep_passes_the_arithmetic_midpoint() {
+        let frequencies = frequencies(SweepMode::Linear, 1001);
+
+        assert_close(frequencies[0], 100f32);
+        assert_close(frequencies[500], 250f32);
+        assert_close(frequencies[1000], 400f32);
+    }
+
+    #[test]
+    fn logarithmic_sweep_passes_the_geometric_midpoint() {
+        let frequencies = frequencies(SweepMode::Logarithmic, 1001);
+
+        assert_close(frequencies[0], 100f32);
+        assert_close(frequencies[500], 200f32);
+        assert_close(frequencies[1000], 400f32);
+    }
+
+    #[test]
+    fn holds_the_end_frequency_after_the_sweep() {
+        for mode in [SweepMode::Linear, SweepMode::Logarithmic] {
+            let frequencies = frequencies(mode, 3000);
+
+            for &frequency in &frequencies[1000..] {
+                assert_close(frequency, 400f32);
+            }
+        }
+    }
+}
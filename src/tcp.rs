@@ -0,0 +1,77 @@
+use {
+    std::io::Write,
+    std::net::{TcpListener, TcpStream},
+    std::sync::mpsc::{self, Receiver, SyncSender},
+};
+
+/// How many lines each client receives per second.
+const BLOCKS_PER_SECOND: u32 = 10;
+
+/// Serves the mono output to any number of TCP clients for external
+/// visualizers. Each line is a JSON array of the consecutive samples since
+/// the previous line, e.g. `[0.0,0.0627,0.1253]`, sent about ten times a
+/// second; in Python, `json.loads(line)` per line of `socket.makefile()`.
+/// Clients may connect and disconnect at any time.
+pub struct SampleStream {
+    sender: SyncSender<Vec<f32>>,
+    block: Vec<f32>,
+    block_length: usize,
+}
+
+impl SampleStream {
+    /// Listens on `address`, e.g. `127.0.0.1:9000`, serving clients from a
+    /// background thread.
+    pub fn serve(address: &str, sample_rate: u32) -> Result<Self, anyhow::Error> {
+        let listener = TcpListener::bind(address)
+            .map_err(|err| anyhow::anyhow!("failed to listen on {}: {}", address, err))?;
+        listener.set_nonblocking(true)?;
+        let (sender, receiver) = mpsc::sync_channel(BLOCKS_PER_SECOND as usize);
+        std::thread::spawn(move || broadcast(listener, receiver));
+        let block_length = (sample_rate / BLOCKS_PER_SECOND).max(1) as usize;
+
+        Ok(Self {
+            sender,
+            block: Vec::with_capacity(block_length),
+            block_length,
+        })
+    }
+
+    /// Queues `sample` for the clients. Never blocks: a block the server
+    /// thread has no room for is dropped.
+    pub fn push(&mut self, sample: f32) {
+        self.block.push(sample);
+
+        if self.block.len() >= self.block_length {
+            let block = std::mem::replace(&mut self.block, Vec::with_capacity(self.block_length));
+            let _ = self.sender.try_send(block);
+        }
+    }
+}
+
+/// Sends every block to every connected client, dropping clients whose
+/// connection fails. Ends once the `SampleStream` is dropped.
+fn broadcast(listener: TcpListener, receiver: Receiver<Vec<f32>>) {
+    let mut clients: Vec<TcpStream> = Vec::new();
+
+    for block in receiver {
+        while let Ok((client, _)) = listener.accept() {
+            if client.set_nonblocking(false).is_ok() {
+                clients.push(client);
+            }
+        }
+
+        // JSON has no NaN or infinity.
+        let values: Vec<String> = block
+            .iter()
+            .map(|sample| {
+                if sample.is_finite() {
+                    sample.to_string()
+                } else {
+                    "0".to_string()
+                }
+            })
+            .collect();
+        let line = format!("[{}]\n", values.join(","));
+        clients.retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+    }
+}
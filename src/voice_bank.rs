@@ -0,0 +1,17 @@
+/// Mixes several independently phased voices, dividing by the voice count so
+/// that a full chord peaks no higher than a single voice.
+pub struct VoiceBank {
+    voices: Vec<Box<dyn FnMut() -> f32 + Send>>,
+}
+
+impl VoiceBank {
+    pub fn new(voices: Vec<Box<dyn FnMut() -> f32 + Send>>) -> Self {
+        Self { voices }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let sum: f32 = self.voices.iter_mut().map(|voice| voice()).sum();
+
+        sum / self.voices.len().max(1) as f32
+    }
+}
@@ -0,0 +1,86 @@
+use {crate::source_file::read_wav, std::path::Path};
+
+/// A single cycle of a waveform, normalized to peak at ±1.0.
+#[derive(Debug, Clone)]
+pub struct Wavetable {
+    samples: Vec<f32>,
+}
+
+impl Wavetable {
+    /// Loads one cycle from a `.csv` file, taking the last comma-separated
+    /// value of each line so that `--csv` output works too, or otherwise from
+    /// the first channel of a WAV file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let samples = if path.extension().is_some_and(|extension| extension == "csv") {
+            load_csv(path)?
+        } else {
+            load_wav(path)?
+        };
+
+        let peak = samples
+            .iter()
+            .fold(0f32, |peak, sample| peak.max(sample.abs()));
+        if peak == 0f32 {
+            return Err(anyhow::anyhow!("{} is silent", path.display()));
+        }
+
+        Ok(Self {
+            samples: samples.iter().map(|sample| sample / peak).collect(),
+        })
+    }
+
+    /// The table at `phase` from 0 to 1, interpolated linearly between
+    /// entries and wrapping from the last entry back to the first.
+    pub fn at(&self, phase: f32) -> f32 {
+        let position = phase.rem_euclid(1f32) * self.samples.len() as f32;
+        let index = position as usize % self.samples.len();
+        let fraction = position.fract();
+        let current = self.samples[index];
+        let next = self.samples[(index + 1) % self.samples.len()];
+
+        current + (next - current) * fraction
+    }
+}
+
+fn load_csv(path: &Path) -> Result<Vec<f32>, anyhow::Error> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| anyhow::anyhow!("failed to read {}: {}", path.display(), err))?;
+    let mut samples = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let value = line.rsplit(',').next().unwrap_or("").trim();
+        if value.is_empty() {
+            continue;
+        }
+
+        match value.parse::<f32>() {
+            Ok(sample) if sample.is_finite() => samples.push(sample),
+            // Allow a header row.
+            _ if index == 0 => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "{} line {}: invalid sample \"{}\"",
+                    path.display(),
+                    index + 1,
+                    value
+                ))
+            }
+        }
+    }
+
+    if samples.is_empty() {
+        return Err(anyhow::anyhow!("{} contains no samples", path.display()));
+    }
+
+    Ok(samples)
+}
+
+fn load_wav(path: &Path) -> Result<Vec<f32>, anyhow::Error> {
+    let (samples, spec) = read_wav(path)?;
+
+    Ok(samples
+        .chunks(spec.channels as usize)
+        .map(|frame| frame[0])
+        .collect())
+}